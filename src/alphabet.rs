@@ -0,0 +1,163 @@
+use core::fmt;
+
+use crate::simple::luts;
+use crate::CodecError;
+
+/// The standard base64 alphabet from RFC 4648 §4
+pub static STANDARD: Alphabet = Alphabet::from_chars(
+    Variant::Standard,
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/",
+);
+
+/// The URL and filename safe alphabet from RFC 4648 §5
+pub static URL_SAFE: Alphabet = Alphabet::from_chars(
+    Variant::UrlSafe,
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+);
+
+/// Selects the SIMD lookup tables used for an alphabet
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Variant {
+    Standard,
+    UrlSafe,
+    /// Any other alphabet, translated with the generic nibble tables
+    Custom,
+}
+
+/// A base64 alphabet, along with the lookup tables used to encode and decode it
+// The SIMD tables are only read by the x86_64 backends
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+pub struct Alphabet {
+    pub(crate) variant: Variant,
+    pub(crate) chars: [u8; 64],
+    pub(crate) e0: [u8; 256],
+    pub(crate) e1: [u8; 256],
+    pub(crate) d: [[u32; 256]; 4],
+    /// 6 bit value -> char, split into four 16 entry pshufb tables
+    pub(crate) nibble_encode: [[u8; 16]; 4],
+    /// ASCII char -> 6 bit value (or 0xff if invalid), split into eight 16 entry pshufb tables
+    pub(crate) nibble_decode: [[u8; 16]; 8],
+}
+
+impl Alphabet {
+    /// Create an alphabet from 64 unique ASCII characters, in value order.
+    ///
+    /// `=` is reserved for padding, and spaces, tabs, CR and LF for line breaks,
+    /// so they can't be part of an alphabet.
+    /// Alphabets matching [`STANDARD`] or [`URL_SAFE`] use their dedicated SIMD tables,
+    /// any other alphabet uses a slightly slower generic SIMD translation.
+    ///
+    /// ```
+    /// use bs64::{Alphabet, EncodeOptions};
+    ///
+    /// // crypt(3) style alphabet
+    /// let alphabet =
+    ///     Alphabet::new(b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz")
+    ///         .unwrap();
+    /// let mut output = [0u8; 8];
+    /// let encoder = EncodeOptions::default().alphabet(&alphabet);
+    /// let len = encoder.encode_mut(b"hello", &mut output).unwrap();
+    /// assert_eq!(&output[..len], b"O4JgP4w=");
+    /// ```
+    pub const fn new(chars: &[u8; 64]) -> Result<Alphabet, CodecError> {
+        let mut i = 0;
+        while i < 64 {
+            let c = chars[i];
+            if !c.is_ascii() || matches!(c, b'=' | b' ' | b'\t' | b'\r' | b'\n') {
+                return Err(CodecError::InvalidAlphabet(i, c));
+            }
+            let mut j = 0;
+            while j < i {
+                if chars[j] == c {
+                    return Err(CodecError::InvalidAlphabet(i, c));
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+
+        let variant = if bytes_eq(chars, &STANDARD.chars) {
+            Variant::Standard
+        } else if bytes_eq(chars, &URL_SAFE.chars) {
+            Variant::UrlSafe
+        } else {
+            Variant::Custom
+        };
+        Ok(Alphabet::from_chars(variant, chars))
+    }
+
+    const fn from_chars(variant: Variant, chars: &[u8; 64]) -> Self {
+        Alphabet {
+            variant,
+            chars: *chars,
+            e0: luts::e0(chars),
+            e1: luts::e1(chars),
+            d: luts::decode(chars),
+            nibble_encode: nibble_encode(chars),
+            nibble_decode: nibble_decode(chars),
+        }
+    }
+
+    /// The 64 characters of the alphabet, in value order
+    pub fn chars(&self) -> &[u8; 64] {
+        &self.chars
+    }
+
+    /// The 6 bit value of a char, or None if it isn't part of the alphabet
+    pub(crate) fn value(&self, c: u8) -> Option<u8> {
+        let x = self.d[3][c as usize];
+        (x < luts::BADCHAR).then_some((x >> 16) as u8)
+    }
+}
+
+impl fmt::Debug for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Alphabet")
+            // Alphabet chars are always ASCII
+            .field(&core::str::from_utf8(&self.chars).unwrap_or_default())
+            .finish()
+    }
+}
+
+impl PartialEq for Alphabet {
+    fn eq(&self, other: &Self) -> bool {
+        self.chars == other.chars
+    }
+}
+
+impl Eq for Alphabet {}
+
+const fn bytes_eq(a: &[u8; 64], b: &[u8; 64]) -> bool {
+    let mut i = 0;
+    while i < 64 {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn nibble_encode(chars: &[u8; 64]) -> [[u8; 16]; 4] {
+    let mut tables = [[0u8; 16]; 4];
+    let mut i = 0;
+    while i < 64 {
+        tables[i >> 4][i & 0x0f] = chars[i];
+        i += 1;
+    }
+    tables
+}
+
+const fn nibble_decode(chars: &[u8; 64]) -> [[u8; 16]; 8] {
+    let mut tables = [[0xffu8; 16]; 8];
+    let mut i = 0;
+    while i < 64 {
+        let c = chars[i] as usize;
+        // Alphabets are always ASCII, this only keeps the indexing below in bounds
+        if c < 128 {
+            tables[c >> 4][c & 0x0f] = i as u8;
+        }
+        i += 1;
+    }
+    tables
+}
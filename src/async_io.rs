@@ -0,0 +1,301 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use crate::{decode_len, CodecError, DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+/// Input bytes encoded, or chars decoded, per call
+const BLOCK_LEN: usize = 3 * 8 * 1024;
+
+/// Async version of [`EncoderWriter`](crate::EncoderWriter), implementing `AsyncWrite`
+/// for tokio and futures-io writers.
+///
+/// The final group and padding are written on `poll_shutdown` (tokio) or `poll_close`
+/// (futures-io), after which the writer can't be written to.
+pub struct AsyncEncoderWriter<'a, W> {
+    encoder: Encoder<'a>,
+    inner: W,
+    /// Encoded output, of which buf[buf_pos..buf_len] is not written yet
+    buf: Vec<u8>,
+    buf_pos: usize,
+    buf_len: usize,
+    /// Set once the final group was encoded
+    finalized: bool,
+}
+
+impl<'a, W> AsyncEncoderWriter<'a, W> {
+    pub fn new(inner: W, options: EncodeOptions<'a>) -> Self {
+        let encoder = Encoder::new(options);
+        // Each write encodes at most one block, along with up to 2 bytes left from the last one
+        let buf_len = encoder.update_len(BLOCK_LEN + 2);
+        AsyncEncoderWriter {
+            encoder,
+            inner,
+            buf: vec![0; buf_len],
+            buf_pos: 0,
+            buf_len: 0,
+            finalized: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Writing to the inner writer directly will corrupt the output
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Write all buffered output with `write`, the inner writer's `poll_write`
+    fn poll_write_buf(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut write: impl FnMut(&mut W, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<()>> {
+        while self.buf_pos < self.buf_len {
+            match ready!(write(
+                &mut self.inner,
+                cx,
+                &self.buf[self.buf_pos..self.buf_len]
+            ))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                n => self.buf_pos += n,
+            }
+        }
+        self.buf_pos = 0;
+        self.buf_len = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_write_input(
+        &mut self,
+        cx: &mut Context<'_>,
+        input: &[u8],
+        write: impl FnMut(&mut W, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<usize>> {
+        if self.finalized {
+            return Poll::Ready(Err(io::Error::other("write after shutdown")));
+        }
+        ready!(self.poll_write_buf(cx, write))?;
+        let len = input.len().min(BLOCK_LEN);
+        self.buf_len = self.encoder.update(&input[..len], &mut self.buf)?;
+        Poll::Ready(Ok(len))
+    }
+
+    /// Write the final group and padding, then all buffered output
+    fn poll_finalize(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut write: impl FnMut(&mut W, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<()>> {
+        if !self.finalized {
+            ready!(self.poll_write_buf(cx, &mut write))?;
+            self.buf_len = self.encoder.finalize(&mut self.buf)?;
+            self.finalized = true;
+        }
+        self.poll_write_buf(cx, write)
+    }
+}
+
+/// Async version of [`DecoderReader`](crate::DecoderReader), implementing `AsyncRead`
+/// for tokio and futures-io readers.
+///
+/// Decode errors are returned as [`io::ErrorKind::InvalidData`],
+/// wrapping a [`CodecError`] with offsets into the whole input.
+/// After a decode error, every later read returns the same error.
+pub struct AsyncDecoderReader<'a, R> {
+    decoder: Decoder<'a>,
+    inner: R,
+    /// Base64 read from the inner reader
+    input: Vec<u8>,
+    /// Decoded bytes, of which output[output_pos..output_len] are not read yet
+    output: Vec<u8>,
+    output_pos: usize,
+    output_len: usize,
+    /// Set once the inner reader reached EOF and all of its input was decoded
+    finished: bool,
+    /// The first decode error, returned by every read after it
+    error: Option<CodecError>,
+}
+
+impl<'a, R> AsyncDecoderReader<'a, R> {
+    pub fn new(inner: R, options: DecodeOptions<'a>) -> Self {
+        AsyncDecoderReader {
+            decoder: Decoder::new(options),
+            inner,
+            input: vec![0; BLOCK_LEN],
+            // Up to 3 chars are left from the last read
+            output: vec![0; decode_len(BLOCK_LEN + 3)],
+            output_pos: 0,
+            output_len: 0,
+            finished: false,
+            error: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Reading from the inner reader directly will corrupt the output
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Decoded bytes not read yet, reading more input with `read`, the inner reader's
+    /// `poll_read`, if there are none. Empty once all input was decoded.
+    fn poll_output(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut read: impl FnMut(&mut R, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<&[u8]>> {
+        if let Some(e) = &self.error {
+            return Poll::Ready(Err(e.duplicate().into()));
+        }
+        while self.output_pos == self.output_len {
+            if self.finished {
+                return Poll::Ready(Ok(&[]));
+            }
+            let read_len = ready!(read(&mut self.inner, cx, &mut self.input))?;
+            let result = if read_len == 0 {
+                self.decoder.finalize(&mut self.output)
+            } else {
+                self.decoder
+                    .update(&self.input[..read_len], &mut self.output)
+            };
+            self.output_len = result.inspect_err(|e| self.error = Some(e.duplicate()))?;
+            self.output_pos = 0;
+            self.finished = read_len == 0;
+        }
+        Poll::Ready(Ok(&self.output[self.output_pos..self.output_len]))
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use super::*;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    fn write<W: AsyncWrite + Unpin>(
+        inner: &mut W,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(inner).poll_write(cx, buf)
+    }
+
+    impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncEncoderWriter<'_, W> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            input: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write_input(cx, input, write)
+        }
+
+        /// Write the buffered output and flush the inner writer.
+        /// A partial group is kept until more input is written, or the writer is shut down.
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_write_buf(cx, write))?;
+            Pin::new(&mut this.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_finalize(cx, write))?;
+            Pin::new(&mut this.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecoderReader<'_, R> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let read = |inner: &mut R, cx: &mut Context<'_>, input: &mut [u8]| {
+                let mut input = ReadBuf::new(input);
+                ready!(Pin::new(inner).poll_read(cx, &mut input))?;
+                Poll::Ready(Ok(input.filled().len()))
+            };
+            if buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            let this = self.get_mut();
+            let output = ready!(this.poll_output(cx, read))?;
+            let len = buf.remaining().min(output.len());
+            buf.put_slice(&output[..len]);
+            this.output_pos += len;
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_io_impl {
+    use super::*;
+    use futures_io::{AsyncRead, AsyncWrite};
+
+    fn write<W: AsyncWrite + Unpin>(
+        inner: &mut W,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(inner).poll_write(cx, buf)
+    }
+
+    impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncEncoderWriter<'_, W> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            input: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write_input(cx, input, write)
+        }
+
+        /// Write the buffered output and flush the inner writer.
+        /// A partial group is kept until more input is written, or the writer is closed.
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_write_buf(cx, write))?;
+            Pin::new(&mut this.inner).poll_flush(cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_finalize(cx, write))?;
+            Pin::new(&mut this.inner).poll_close(cx)
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecoderReader<'_, R> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let read = |inner: &mut R, cx: &mut Context<'_>, input: &mut [u8]| {
+                Pin::new(inner).poll_read(cx, input)
+            };
+            if buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            let this = self.get_mut();
+            let output = ready!(this.poll_output(cx, read))?;
+            let len = buf.len().min(output.len());
+            buf[..len].copy_from_slice(&output[..len]);
+            this.output_pos += len;
+            Poll::Ready(Ok(len))
+        }
+    }
+}
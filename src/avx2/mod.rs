@@ -1,7 +1,11 @@
 #[cfg(target_arch = "x86_64")]
-use std::arch::x86_64::*;
+use core::arch::x86_64::*;
+use core::mem::MaybeUninit;
 
-use crate::CodecError;
+use crate::alphabet::{Alphabet, Variant, STANDARD};
+use crate::{as_uninit, check_output_len, copy_to_uninit};
+use crate::{decode_len_no_pad, encode_len, encode_len_no_pad};
+use crate::{Backend, CodecError, LineWrap};
 
 use super::simple;
 
@@ -37,6 +41,7 @@ use super::simple;
 // NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+#[inline(always)]
 unsafe fn enc_reshuffle(input: __m256i) -> __m256i {
     // translation from SSE into AVX2 of procedure
     // https://github.com/WojciechMula/base64simd/blob/master/encode/unpack_bigendian.cpp
@@ -57,32 +62,147 @@ unsafe fn enc_reshuffle(input: __m256i) -> __m256i {
     _mm256_or_si256(t1, t3)
 }
 
-unsafe fn enc_translate(input: __m256i) -> __m256i {
-    let lut: __m256i = _mm256_setr_epi8(
-        65, 71, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -19, -16, 0, 0, 65, 71, -4, -4, -4, -4, -4,
-        -4, -4, -4, -4, -4, -19, -16, 0, 0,
-    );
-    let mut indices = _mm256_subs_epu8(input, _mm256_set1_epi8(51));
-    let mask = _mm256_cmpgt_epi8(input, _mm256_set1_epi8(25));
-    indices = _mm256_sub_epi8(indices, mask);
+/// Translation from 6 bit values to chars in the encode loop
+enum EncodeLut {
+    /// Offsets added to each 6 bit value, selected by range:
+    /// A..Z, a..z, 0..9 (x10), then the two alphabet specific chars
+    Offsets(__m256i),
+    /// Direct lookup of any alphabet, one table per 16 values
+    Nibbles([__m256i; 4]),
+}
 
-    _mm256_add_epi8(input, _mm256_shuffle_epi8(lut, indices))
+#[inline(always)]
+unsafe fn enc_lut(alphabet: &Alphabet) -> EncodeLut {
+    match alphabet.variant {
+        Variant::Standard => EncodeLut::Offsets(_mm256_setr_epi8(
+            65, 71, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -19, -16, 0, 0, 65, 71, -4, -4, -4, -4,
+            -4, -4, -4, -4, -4, -4, -19, -16, 0, 0,
+        )),
+        Variant::UrlSafe => EncodeLut::Offsets(_mm256_setr_epi8(
+            65, 71, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -17, 32, 0, 0, 65, 71, -4, -4, -4, -4,
+            -4, -4, -4, -4, -4, -4, -17, 32, 0, 0,
+        )),
+        Variant::Custom => EncodeLut::Nibbles(alphabet.nibble_encode.map(|t| broadcast_table(&t))),
+    }
 }
 
-pub fn encode_with_fallback(dest: &mut [u8], str: &[u8]) -> usize {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { encode(dest, str) }
-    } else {
-        simple::encode(str, dest)
+#[inline(always)]
+unsafe fn broadcast_table(table: &[u8; 16]) -> __m256i {
+    _mm256_broadcastsi128_si256(_mm_loadu_si128(table.as_ptr() as *const __m128i))
+}
+
+#[inline(always)]
+unsafe fn enc_translate(input: __m256i, lut: &EncodeLut) -> __m256i {
+    match lut {
+        EncodeLut::Offsets(lut) => {
+            let mut indices = _mm256_subs_epu8(input, _mm256_set1_epi8(51));
+            let mask = _mm256_cmpgt_epi8(input, _mm256_set1_epi8(25));
+            indices = _mm256_sub_epi8(indices, mask);
+
+            _mm256_add_epi8(input, _mm256_shuffle_epi8(*lut, indices))
+        }
+        EncodeLut::Nibbles(tables) => {
+            // pshufb only looks at the low nibble, so look up all four tables
+            // and keep the one selected by the high bits of each value
+            let hi = _mm256_and_si256(_mm256_srli_epi16(input, 4), _mm256_set1_epi8(0x03));
+            let mut out = _mm256_shuffle_epi8(tables[0], input);
+            for (i, table) in tables.iter().enumerate().skip(1) {
+                let select = _mm256_cmpeq_epi8(hi, _mm256_set1_epi8(i as i8));
+                out = _mm256_blendv_epi8(out, _mm256_shuffle_epi8(*table, input), select);
+            }
+            out
+        }
     }
 }
 
-/// Encode a slice of bytes into base64 using avx2 instructions
+/// Whether avx2 can be used, always true when it's enabled at compile time
+#[cfg(target_feature = "avx2")]
+#[inline(always)]
+pub fn has_avx2() -> bool {
+    true
+}
+
+/// Whether avx2 can be used, detected at runtime
+#[cfg(not(target_feature = "avx2"))]
+#[inline]
+pub fn has_avx2() -> bool {
+    #[cfg(feature = "std")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        crate::detect::has_avx2()
+    }
+}
+
+/// Encode with padding, with avx2 if it's available, see [`Backend::detect`]
+///
+/// # Panics
+/// If dest is shorter than `encode_len(str.len())`
+pub fn encode_with_fallback(dest: &mut [u8], str: &[u8]) -> usize {
+    let len = encode_len(str.len());
+    assert!(
+        dest.len() >= len,
+        "output length {} is < expected length {len}",
+        dest.len()
+    );
+    // Safety: encoding only writes chars to dest
+    let len = (Backend::detect().fns().encode)(unsafe { as_uninit(dest) }, str, &STANDARD);
+    simple::pad(dest, len)
+}
+
+/// Encode with the given alphabet without padding, with avx2 if it's available.
+/// Fails if dest is shorter than `encode_len_no_pad(str.len())`.
+pub fn encode_with_alphabet_with_fallback(
+    dest: &mut [u8],
+    str: &[u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    check_output_len(dest.len(), encode_len_no_pad(str.len()))?;
+    // Safety: encoding only writes chars to dest
+    Ok((Backend::detect().fns().encode)(
+        unsafe { as_uninit(dest) },
+        str,
+        alphabet,
+    ))
+}
+
+/// Encode a slice of bytes into base64 using avx2 instructions and the standard alphabet,
+/// with padding
 ///
 /// # Safety
 /// - Must only be executed on avx2 enabled cpus
+/// - dest must be at least `encode_len(str.len())` long
 #[target_feature(enable = "avx2")]
 pub unsafe fn encode(dest: &mut [u8], str: &[u8]) -> usize {
+    let len = encode_with_alphabet(dest, str, &STANDARD);
+    simple::pad(dest, len)
+}
+
+/// Encode a slice of bytes into base64 using avx2 instructions and the given alphabet,
+/// without padding
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+/// - dest must be at least `encode_len_no_pad(str.len())` long
+#[target_feature(enable = "avx2")]
+pub unsafe fn encode_with_alphabet(dest: &mut [u8], str: &[u8], alphabet: &Alphabet) -> usize {
+    encode_with_alphabet_uninit(as_uninit(dest), str, alphabet)
+}
+
+/// [`encode_with_alphabet`] into a possibly uninitialized dest
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+/// - dest must be at least `encode_len_no_pad(str.len())` long
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn encode_with_alphabet_uninit(
+    dest: &mut [MaybeUninit<u8>],
+    str: &[u8],
+    alphabet: &Alphabet,
+) -> usize {
+    let lut = enc_lut(alphabet);
     let mut str_offset: isize = 0;
     let mut dest_offset = 0;
 
@@ -106,9 +226,9 @@ pub unsafe fn encode(dest: &mut [u8], str: &[u8]) -> usize {
 
         loop {
             inputvector = enc_reshuffle(inputvector);
-            inputvector = enc_translate(inputvector);
+            inputvector = enc_translate(inputvector, &lut);
             _mm256_storeu_si256(
-                dest.as_ptr().offset(dest_offset) as *mut __m256i,
+                dest.as_mut_ptr().offset(dest_offset) as *mut __m256i,
                 inputvector,
             );
             str_offset += 24;
@@ -122,18 +242,184 @@ pub unsafe fn encode(dest: &mut [u8], str: &[u8]) -> usize {
         }
     }
 
-    if str_len - str_offset == 0 {
-        return dest_offset as usize;
+    let mut dest_offset = dest_offset as usize;
+    for chunk in str[str_offset as usize..].chunks(24) {
+        dest_offset += encode_partial(&mut dest[dest_offset..], chunk, &lut);
+    }
+    dest_offset
+}
+
+/// Encode up to 24 bytes with masked loads and stores, which stay in bounds
+#[inline(always)]
+unsafe fn encode_partial(dest: &mut [MaybeUninit<u8>], str: &[u8], lut: &EncodeLut) -> usize {
+    // Like the main loop, the first 4 bytes of the vector are not used.
+    // Zero bytes after the input give the zero trailing bits of a partial group.
+    let inputvector = load_partial(str, 1, 0);
+    let outputvector = enc_translate(enc_reshuffle(inputvector), lut);
+    let len = encode_len_no_pad(str.len());
+    store_partial(dest, outputvector, len);
+    len
+}
+
+/// Mask of the 32 bit lanes from `start` to `end`
+#[inline(always)]
+unsafe fn lane_mask(start: usize, end: usize) -> __m256i {
+    let lanes = _mm256_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7);
+    _mm256_andnot_si256(
+        _mm256_cmpgt_epi32(_mm256_set1_epi32(start as i32), lanes),
+        _mm256_cmpgt_epi32(_mm256_set1_epi32(end as i32), lanes),
+    )
+}
+
+/// Load src into a vector starting at 32 bit lane `first_lane`, all other bytes set to `fill`.
+/// Whole lanes are loaded with a masked load, and the last 1 to 3 bytes are inserted separately,
+/// so nothing past src is read.
+#[inline(always)]
+unsafe fn load_partial(src: &[u8], first_lane: usize, fill: u8) -> __m256i {
+    let lanes = src.len() / 4;
+    let mask = lane_mask(first_lane, first_lane + lanes);
+    // Lanes before first_lane are masked out, so the pointer is never dereferenced there
+    let ptr = src.as_ptr().wrapping_sub(first_lane * 4) as *const i32;
+    let vector = _mm256_blendv_epi8(
+        _mm256_set1_epi8(fill as i8),
+        _mm256_maskload_epi32(ptr, mask),
+        mask,
+    );
+
+    let rest = &src[lanes * 4..];
+    if rest.is_empty() {
+        return vector;
+    }
+    // Built in a register, going through a byte array would stall on store forwarding
+    let fill = u32::from(fill) * 0x0101_0101;
+    let last = match *rest {
+        [a] => u32::from(a) | fill & 0xffff_ff00,
+        [a, b] => u32::from(u16::from_le_bytes([a, b])) | fill & 0xffff_0000,
+        [a, b, c] => u32::from_le_bytes([a, b, c, 0]) | fill & 0xff00_0000,
+        _ => unreachable!(),
+    };
+    let lane = first_lane + lanes;
+    _mm256_blendv_epi8(
+        vector,
+        _mm256_set1_epi32(last as i32),
+        lane_mask(lane, lane + 1),
+    )
+}
+
+/// Store the first `len` bytes of a vector.
+/// Whole lanes are stored with a masked store, and the last 1 to 3 bytes are written separately,
+/// so nothing past `dest[..len]` is written.
+#[inline(always)]
+unsafe fn store_partial(dest: &mut [MaybeUninit<u8>], vector: __m256i, len: usize) {
+    let dest = &mut dest[..len];
+    let lanes = len / 4;
+    _mm256_maskstore_epi32(dest.as_mut_ptr() as *mut i32, lane_mask(0, lanes), vector);
+
+    let rest = &mut dest[lanes * 4..];
+    if !rest.is_empty() {
+        let last = _mm256_permutevar8x32_epi32(vector, _mm256_set1_epi32(lanes as i32));
+        let last = _mm256_cvtsi256_si32(last).to_le_bytes();
+        for (byte, &c) in rest.iter_mut().zip(&last) {
+            byte.write(c);
+        }
     }
+}
+
+/// Encode with line wrapping, with avx2 if it's available.
+/// Fails if dest is shorter than `wrap.wrapped_len(encode_len_no_pad(str.len()))`.
+pub fn encode_wrapped_with_fallback(
+    dest: &mut [u8],
+    str: &[u8],
+    alphabet: &Alphabet,
+    wrap: LineWrap,
+) -> Result<usize, CodecError> {
+    check_output_len(dest.len(), wrap.wrapped_len(encode_len_no_pad(str.len())))?;
+    let encode_wrapped = Backend::detect().fns().encode_wrapped;
+    // Safety: encoding only writes chars and line endings to dest
+    Ok(encode_wrapped(
+        unsafe { as_uninit(dest) },
+        str,
+        alphabet,
+        wrap,
+    ))
+}
+
+/// Encode a slice of bytes into line wrapped base64 using avx2 instructions, without padding
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+/// - dest must be at least `wrap.wrapped_len(encode_len_no_pad(str.len()))` long
+#[target_feature(enable = "avx2")]
+pub unsafe fn encode_wrapped(
+    dest: &mut [u8],
+    str: &[u8],
+    alphabet: &Alphabet,
+    wrap: LineWrap,
+) -> usize {
+    encode_wrapped_uninit(as_uninit(dest), str, alphabet, wrap)
+}
+
+/// [`encode_wrapped`] into a possibly uninitialized dest
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+/// - dest must be at least `wrap.wrapped_len(encode_len_no_pad(str.len()))` long
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn encode_wrapped_uninit(
+    dest: &mut [MaybeUninit<u8>],
+    str: &[u8],
+    alphabet: &Alphabet,
+    wrap: LineWrap,
+) -> usize {
+    let lut = enc_lut(alphabet);
+    let ending = wrap.ending().as_bytes();
+    let line_groups = wrap.line_len() / 4;
+
+    let mut str_offset = 0;
+    let mut dest_offset = 0;
+    // Groups of 4 chars written to the current line
+    let mut line_pos = 0;
+
+    while str.len() - str_offset >= 32 {
+        let inputvector = if str_offset >= 4 {
+            _mm256_loadu_si256(str.as_ptr().add(str_offset - 4) as *const __m256i)
+        } else {
+            // the first 4 bytes of the vector are not used
+            let mut buf = [0u8; 32];
+            buf[4..].copy_from_slice(&str[str_offset..str_offset + 28]);
+            _mm256_loadu_si256(buf.as_ptr() as *const __m256i)
+        };
+        let outputvector = enc_translate(enc_reshuffle(inputvector), &lut);
+        _mm256_storeu_si256(
+            dest.as_mut_ptr().add(dest_offset) as *mut __m256i,
+            outputvector,
+        );
 
-    dest_offset += simple::encode(
-        &str[str_offset as usize..],
-        &mut dest[dest_offset as usize..],
-    ) as isize;
+        // Only keep the groups that fit on the current line,
+        // anything past them is overwritten by the line ending and the next store
+        let groups = (line_groups - line_pos).min(8);
+        str_offset += groups * 3;
+        dest_offset += groups * 4;
+        line_pos += groups;
+
+        if line_pos == line_groups && str_offset < str.len() {
+            copy_to_uninit(&mut dest[dest_offset..], ending);
+            dest_offset += ending.len();
+            line_pos = 0;
+        }
+    }
 
-    dest_offset as usize
+    dest_offset
+        + simple::encode_wrapped_from(
+            &str[str_offset..],
+            &mut dest[dest_offset..],
+            alphabet,
+            wrap,
+            line_pos * 4,
+        )
 }
 
+#[inline(always)]
 unsafe fn dec_reshuffle(input: __m256i) -> __m256i {
     // inlined procedure pack_madd from https://github.com/WojciechMula/base64simd/blob/master/decode/pack.avx2.cpp
     // The only difference is that elements are reversed,
@@ -155,79 +441,285 @@ unsafe fn dec_reshuffle(input: __m256i) -> __m256i {
     _mm256_permutevar8x32_epi32(out, _mm256_setr_epi32(0, 1, 2, 4, 5, 6, -1, -1))
 }
 
+/// Decode padded base64 with avx2 if it's available
 pub fn decode_with_fallback(dest: &mut [u8], str: &[u8]) -> Result<usize, CodecError> {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { decode(dest, str) }
-    } else {
-        simple::decode(str, dest)
+    decode_with_alphabet_with_fallback(dest, simple::unpad(str)?, &STANDARD)
+}
+
+/// Decode unpadded base64 with the given alphabet, with avx2 if it's available.
+/// Fails if dest is shorter than `decode_len_no_pad(str.len())`.
+pub fn decode_with_alphabet_with_fallback(
+    dest: &mut [u8],
+    str: &[u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    check_output_len(dest.len(), decode_len_no_pad(str.len()))?;
+    // Safety: decoding only writes bytes to dest
+    (Backend::detect().fns().decode)(unsafe { as_uninit(dest) }, str, alphabet)
+}
+
+/// Remove whitespace with avx2 if it's available
+pub fn compact_whitespace_with_fallback(dest: &mut [u8], str: &[u8]) -> usize {
+    (Backend::detect().fns().compact_whitespace)(dest, str)
+}
+
+/// pshufb indices that move the bytes not set in an 8 bit mask to the front
+pub(crate) static COMPACT_LUT: [[u8; 8]; 256] = compact_lut();
+
+const fn compact_lut() -> [[u8; 8]; 256] {
+    let mut lut = [[0x80u8; 8]; 256];
+    let mut mask = 0;
+    while mask < 256 {
+        let mut i = 0;
+        let mut len = 0;
+        while i < 8 {
+            if mask & (1 << i) == 0 {
+                lut[mask][len] = i as u8;
+                len += 1;
+            }
+            i += 1;
+        }
+        mask += 1;
     }
+    lut
 }
 
-/// Decode a slice of bytes into base64 using avx2 instructions
+/// Copy a slice to dest, skipping spaces, tabs, CR and LF, using avx2 instructions
+/// dest must be at least as long as str
+/// Returns the number of bytes written to dest
 ///
 /// # Safety
 /// - Must only be executed on avx2 enabled cpus
 #[target_feature(enable = "avx2")]
-pub unsafe fn decode(out: &mut [u8], src: &[u8]) -> Result<usize, CodecError> {
-    let mut src_i: isize = 0;
-    let mut dest_i: isize = 0;
+pub unsafe fn compact_whitespace(dest: &mut [u8], str: &[u8]) -> usize {
+    assert!(dest.len() >= str.len());
+    let mut str_i = 0;
+    let mut dest_i = 0;
+
+    // dest_i <= str_i, so there is always room for a full store
+    while str.len() - str_i >= 32 {
+        let input = _mm256_loadu_si256(str.as_ptr().add(str_i) as *const __m256i);
+        let whitespace = _mm256_or_si256(
+            _mm256_or_si256(
+                _mm256_cmpeq_epi8(input, _mm256_set1_epi8(b' ' as i8)),
+                _mm256_cmpeq_epi8(input, _mm256_set1_epi8(b'\t' as i8)),
+            ),
+            _mm256_or_si256(
+                _mm256_cmpeq_epi8(input, _mm256_set1_epi8(b'\r' as i8)),
+                _mm256_cmpeq_epi8(input, _mm256_set1_epi8(b'\n' as i8)),
+            ),
+        );
+        let mask = _mm256_movemask_epi8(whitespace) as u32;
+
+        if mask == 0 {
+            _mm256_storeu_si256(dest.as_mut_ptr().add(dest_i) as *mut __m256i, input);
+            dest_i += 32;
+        } else {
+            // Compact each 8 byte piece with a lookup table
+            for piece in 0..4 {
+                let piece_mask = (mask >> (piece * 8)) as u8;
+                let bytes = _mm_loadl_epi64(str.as_ptr().add(str_i + piece * 8) as *const __m128i);
+                let shuffle =
+                    _mm_loadl_epi64(COMPACT_LUT[piece_mask as usize].as_ptr() as *const __m128i);
+                _mm_storel_epi64(
+                    dest.as_mut_ptr().add(dest_i) as *mut __m128i,
+                    _mm_shuffle_epi8(bytes, shuffle),
+                );
+                dest_i += 8 - piece_mask.count_ones() as usize;
+            }
+        }
+        str_i += 32;
+    }
 
-    while src.len() - src_i as usize >= 45 {
-        // The input consists of six character sets in the Base64 alphabet,
-        // which we need to map back to the 6-bit values they represent.
-        // There are three ranges, two singles, and then there's the rest.
-        //
-        //  #  From       To        Add  Characters
-        //  1  [43]       [62]      +19  +
-        //  2  [47]       [63]      +16  /
-        //  3  [48..57]   [52..61]   +4  0..9
-        //  4  [65..90]   [0..25]   -65  A..Z
-        //  5  [97..122]  [26..51]  -71  a..z
-        // (6) Everything else => invalid input
+    dest_i + simple::compact_whitespace(&str[str_i..], &mut dest[dest_i..])
+}
 
-        let str = _mm256_loadu_si256(src.as_ptr().offset(src_i) as *const __m256i);
+/// Lookup tables used to validate and translate chars in the decode loop
+enum DecodeLuts {
+    /// Classify chars by nibble, then add an offset selected by the high nibble
+    Roll {
+        lut_lo: __m256i,
+        lut_hi: __m256i,
+        lut_roll: __m256i,
+        // The char that shares its high nibble with other valid chars,
+        // and the adjustment applied to its lut_roll index
+        special: __m256i,
+        special_adjust: __m256i,
+    },
+    /// Direct lookup of any ASCII alphabet, one table per high nibble.
+    /// Invalid chars map to 0xff.
+    Nibbles([__m256i; 8]),
+}
 
+#[inline(always)]
+unsafe fn dec_luts(alphabet: &Alphabet) -> DecodeLuts {
+    match alphabet.variant {
         // code by @aqrit from
         // https://github.com/WojciechMula/base64simd/issues/3#issuecomment-271137490
         // transated into AVX2
-        let lut_lo: __m256i = _mm256_setr_epi8(
-            0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x1A, 0x1B, 0x1B,
-            0x1B, 0x1A, 0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x1A,
-            0x1B, 0x1B, 0x1B, 0x1A,
-        );
-        let lut_hi: __m256i = _mm256_setr_epi8(
-            0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x08, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
-            0x10, 0x10, 0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x08, 0x10, 0x10, 0x10, 0x10,
-            0x10, 0x10, 0x10, 0x10,
-        );
-        let lut_roll: __m256i = _mm256_setr_epi8(
-            0, 16, 19, 4, -65, -65, -71, -71, 0, 0, 0, 0, 0, 0, 0, 0, 0, 16, 19, 4, -65, -65, -71,
-            -71, 0, 0, 0, 0, 0, 0, 0, 0,
-        );
-
-        let mask_2f: __m256i = _mm256_set1_epi8(0x2f);
+        Variant::Standard => DecodeLuts::Roll {
+            lut_lo: _mm256_setr_epi8(
+                0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x1A, 0x1B, 0x1B,
+                0x1B, 0x1A, 0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x1A,
+                0x1B, 0x1B, 0x1B, 0x1A,
+            ),
+            lut_hi: _mm256_setr_epi8(
+                0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x08, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+                0x10, 0x10, 0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x08, 0x10, 0x10, 0x10, 0x10,
+                0x10, 0x10, 0x10, 0x10,
+            ),
+            lut_roll: _mm256_setr_epi8(
+                0, 16, 19, 4, -65, -65, -71, -71, 0, 0, 0, 0, 0, 0, 0, 0, 0, 16, 19, 4, -65, -65,
+                -71, -71, 0, 0, 0, 0, 0, 0, 0, 0,
+            ),
+            // '/' moves from high nibble 2 to the free slot 1
+            special: _mm256_set1_epi8(0x2f),
+            special_adjust: _mm256_set1_epi8(-1),
+        },
+        // Same scheme with the classes reworked for '-' and '_'.
+        // '_' is the only valid char in row 5 above 'Z', and 'a'..'z' no longer
+        // share a class with row 5, so row 7 gets its own bit (0x20).
+        Variant::UrlSafe => DecodeLuts::Roll {
+            lut_lo: _mm256_setr_epi8(
+                0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x3B, 0x3B, 0x3A,
+                0x3B, 0x33, 0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x3B,
+                0x3B, 0x3A, 0x3B, 0x33,
+            ),
+            lut_hi: _mm256_setr_epi8(
+                0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x20, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+                0x10, 0x10, 0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x20, 0x10, 0x10, 0x10, 0x10,
+                0x10, 0x10, 0x10, 0x10,
+            ),
+            lut_roll: _mm256_setr_epi8(
+                0, 0, 17, 4, -65, -65, -71, -71, -32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 17, 4, -65, -65,
+                -71, -71, -32, 0, 0, 0, 0, 0, 0, 0,
+            ),
+            // '_' moves from high nibble 5 to the free slot 8
+            special: _mm256_set1_epi8(0x5f),
+            special_adjust: _mm256_set1_epi8(3),
+        },
+        Variant::Custom => DecodeLuts::Nibbles(alphabet.nibble_decode.map(|t| broadcast_table(&t))),
+    }
+}
 
-        // lookup
-        let hi_nibbles: __m256i = _mm256_srli_epi32(str, 4);
-        let lo_nibbles: __m256i = _mm256_and_si256(str, mask_2f);
+/// Translate chars to their 6 bit values,
+/// or return a mask with a bit set for each byte of `str` that is not part of the alphabet
+#[inline(always)]
+unsafe fn dec_translate(str: __m256i, luts: &DecodeLuts) -> Result<__m256i, u32> {
+    match luts {
+        DecodeLuts::Roll {
+            lut_lo,
+            lut_hi,
+            lut_roll,
+            special,
+            special_adjust,
+        } => {
+            // The input consists of six character sets in the Base64 alphabet,
+            // which we need to map back to the 6-bit values they represent.
+            // There are three ranges, two singles, and then there's the rest.
+            //
+            //  #  From       To        Add  Characters
+            //  1  [43]       [62]      +19  +            (URL safe: [45] +17 -)
+            //  2  [47]       [63]      +16  /            (URL safe: [95]  -32 _)
+            //  3  [48..57]   [52..61]   +4  0..9
+            //  4  [65..90]   [0..25]   -65  A..Z
+            //  5  [97..122]  [26..51]  -71  a..z
+            // (6) Everything else => invalid input
+
+            let mask_2f: __m256i = _mm256_set1_epi8(0x2f);
+
+            // lookup
+            let hi_nibbles: __m256i = _mm256_srli_epi32(str, 4);
+            let lo_nibbles: __m256i = _mm256_and_si256(str, mask_2f);
+
+            let lo: __m256i = _mm256_shuffle_epi8(*lut_lo, lo_nibbles);
+            let eq_special: __m256i = _mm256_cmpeq_epi8(str, *special);
+            let eq_special = _mm256_and_si256(eq_special, *special_adjust);
+
+            let hi_nibbles = _mm256_and_si256(hi_nibbles, mask_2f);
+            let hi: __m256i = _mm256_shuffle_epi8(*lut_hi, hi_nibbles);
+            let roll: __m256i =
+                _mm256_shuffle_epi8(*lut_roll, _mm256_add_epi8(eq_special, hi_nibbles));
+
+            if _mm256_testz_si256(lo, hi) == 0 {
+                let valid = _mm256_cmpeq_epi8(_mm256_and_si256(lo, hi), _mm256_setzero_si256());
+                return Err(!(_mm256_movemask_epi8(valid) as u32));
+            }
 
-        let lo: __m256i = _mm256_shuffle_epi8(lut_lo, lo_nibbles);
-        let eq_2f: __m256i = _mm256_cmpeq_epi8(str, mask_2f);
+            Ok(_mm256_add_epi8(str, roll))
+        }
+        DecodeLuts::Nibbles(tables) => {
+            // Chars >= 0x80 match none of the tables and stay invalid
+            let hi = _mm256_and_si256(_mm256_srli_epi16(str, 4), _mm256_set1_epi8(0x0f));
+            let mut out = _mm256_set1_epi8(-1);
+            for (i, table) in tables.iter().enumerate() {
+                let select = _mm256_cmpeq_epi8(hi, _mm256_set1_epi8(i as i8));
+                out = _mm256_blendv_epi8(out, _mm256_shuffle_epi8(*table, str), select);
+            }
 
-        let hi_nibbles = _mm256_and_si256(hi_nibbles, mask_2f);
-        let hi: __m256i = _mm256_shuffle_epi8(lut_hi, hi_nibbles);
-        let roll: __m256i = _mm256_shuffle_epi8(lut_roll, _mm256_add_epi8(eq_2f, hi_nibbles));
+            let invalid = _mm256_movemask_epi8(out) as u32;
+            if invalid != 0 {
+                return Err(invalid);
+            }
 
-        if _mm256_testz_si256(lo, hi) == 0 {
-            break;
+            Ok(out)
         }
+    }
+}
 
-        let str = _mm256_add_epi8(str, roll);
-        // end of copied function
+/// Decode a slice of base64 using avx2 instructions and the standard alphabet
+/// Up to two padding chars are accepted, and the input length must be a multiple of 4
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+/// - out must be at least `decode_len_no_pad(src.len())` long, without counting padding
+#[target_feature(enable = "avx2")]
+pub unsafe fn decode(out: &mut [u8], src: &[u8]) -> Result<usize, CodecError> {
+    decode_with_alphabet(out, simple::unpad(src)?, &STANDARD)
+}
 
-        src_i += 32;
+/// Decode a slice of unpadded base64 using avx2 instructions and the given alphabet.
+/// An invalid char is reported with its offset as soon as the vector containing it is decoded.
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+/// - out must be at least `decode_len_no_pad(src.len())` long
+#[target_feature(enable = "avx2")]
+pub unsafe fn decode_with_alphabet(
+    out: &mut [u8],
+    src: &[u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    decode_with_alphabet_uninit(as_uninit(out), src, alphabet)
+}
+
+/// [`decode_with_alphabet`] into a possibly uninitialized output
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+/// - out must be at least `decode_len_no_pad(src.len())` long
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn decode_with_alphabet_uninit(
+    out: &mut [MaybeUninit<u8>],
+    src: &[u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    // Checked first, like simple does, so the error doesn't depend on the input's content
+    if src.len() % 4 == 1 {
+        return Err(CodecError::InputModError(src.len()));
+    }
+
+    let luts = dec_luts(alphabet);
+    let mut src_i: isize = 0;
+    let mut dest_i: isize = 0;
 
-        // end of inlined function
+    while src.len() - src_i as usize >= 45 {
+        let str = _mm256_loadu_si256(src.as_ptr().offset(src_i) as *const __m256i);
+
+        let str = dec_translate(str, &luts)
+            .map_err(|invalid| invalid_byte(src, src_i as usize, invalid))?;
+
+        src_i += 32;
 
         // Reshuffle the input to packed 12-byte output format:
         let str = dec_reshuffle(str);
@@ -235,6 +727,79 @@ pub unsafe fn decode(out: &mut [u8], src: &[u8]) -> Result<usize, CodecError> {
         dest_i += 24;
     }
 
-    let end_decode_len = simple::decode(&src[src_i as usize..], &mut out[dest_i as usize..])?;
-    Ok(dest_i as usize + end_decode_len)
+    // Decode the tail with masked loads and stores
+    let mut src_i = src_i as usize;
+    let mut dest_i = dest_i as usize;
+    for chunk in src[src_i..].chunks(32) {
+        let str = load_partial(chunk, 0, alphabet.chars()[0]);
+        let str = dec_translate(str, &luts).map_err(|invalid| invalid_byte(src, src_i, invalid))?;
+        let len = decode_len_no_pad(chunk.len());
+        store_partial(&mut out[dest_i..], dec_reshuffle(str), len);
+        src_i += chunk.len();
+        dest_i += len;
+    }
+    Ok(dest_i)
+}
+
+/// Check that every char of unpadded `src` is part of the alphabet using avx2 instructions,
+/// without decoding it. Its length isn't checked.
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+#[target_feature(enable = "avx2")]
+pub unsafe fn validate(src: &[u8], alphabet: &Alphabet) -> Result<(), CodecError> {
+    let luts = dec_luts(alphabet);
+    // Only the classification is left of dec_translate once its result is dropped
+    let mut chunks = src.chunks_exact(32);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let str = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+        dec_translate(str, &luts).map_err(|invalid| invalid_byte(src, i * 32, invalid))?;
+    }
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        let str = load_partial(rest, 0, alphabet.chars()[0]);
+        let offset = src.len() - rest.len();
+        dec_translate(str, &luts).map_err(|invalid| invalid_byte(src, offset, invalid))?;
+    }
+    Ok(())
+}
+
+/// Convert the chars of unpadded `buf` from one alphabet to another in place using avx2
+/// instructions, checking that they're part of `from`. Its length isn't checked.
+///
+/// # Safety
+/// - Must only be executed on avx2 enabled cpus
+#[target_feature(enable = "avx2")]
+pub unsafe fn transcode(buf: &mut [u8], from: &Alphabet, to: &Alphabet) -> Result<(), CodecError> {
+    let luts = dec_luts(from);
+    let lut = enc_lut(to);
+    let mut i = 0;
+
+    // Chars are translated to their values and straight back to chars of the other alphabet
+    while buf.len() - i >= 32 {
+        let ptr = buf.as_mut_ptr().add(i) as *mut __m256i;
+        let values = dec_translate(_mm256_loadu_si256(ptr), &luts)
+            .map_err(|invalid| invalid_byte(buf, i, invalid))?;
+        _mm256_storeu_si256(ptr, enc_translate(values, &lut));
+        i += 32;
+    }
+
+    if i < buf.len() {
+        let values = dec_translate(load_partial(&buf[i..], 0, from.chars()[0]), &luts)
+            .map_err(|invalid| invalid_byte(buf, i, invalid))?;
+        let len = buf.len() - i;
+        store_partial(as_uninit(&mut buf[i..]), enc_translate(values, &lut), len);
+    }
+    Ok(())
+}
+
+/// Error for the first invalid byte of the vector loaded from `src[offset..]`.
+/// Bytes past the end of `src` are valid fillers, so it's always in bounds.
+#[cold]
+fn invalid_byte(src: &[u8], offset: usize, invalid: u32) -> CodecError {
+    let offset = offset + invalid.trailing_zeros() as usize;
+    CodecError::InvalidByte {
+        offset,
+        byte: src[offset],
+    }
 }
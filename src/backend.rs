@@ -0,0 +1,218 @@
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::alphabet::Alphabet;
+use crate::simple::TranscodeTable;
+#[cfg(target_arch = "x86_64")]
+use crate::{avx2, sse};
+use crate::{simple, CodecError, LineWrap};
+
+#[cfg(any(
+    all(feature = "backend-scalar", feature = "backend-sse"),
+    all(feature = "backend-scalar", feature = "backend-avx2"),
+    all(feature = "backend-sse", feature = "backend-avx2"),
+))]
+compile_error!(
+    "only one of the backend-scalar, backend-sse and backend-avx2 features can be enabled"
+);
+
+#[cfg(all(
+    any(feature = "backend-sse", feature = "backend-avx2"),
+    not(target_arch = "x86_64")
+))]
+compile_error!("the backend-sse and backend-avx2 features need target_arch = \"x86_64\"");
+
+/// Implementation used to encode and decode.
+///
+/// By default the fastest available backend is detected once, see [`Backend::detect`],
+/// and can be forced with [`EncodeOptions::backend`](crate::EncodeOptions::backend) and
+/// [`DecodeOptions::backend`](crate::DecodeOptions::backend).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Portable scalar code, see [`simple`]
+    Scalar,
+    /// Portable scalar code, decoding with [`simple::decode_iter_with_alphabet`]
+    ScalarIter,
+    /// SSSE3 on x86_64, see [`sse`]
+    Sse,
+    /// AVX2 on x86_64, see [`avx2`]
+    Avx2,
+}
+
+type EncodeFn = fn(&mut [MaybeUninit<u8>], &[u8], &Alphabet) -> usize;
+type EncodeWrappedFn = fn(&mut [MaybeUninit<u8>], &[u8], &Alphabet, LineWrap) -> usize;
+type DecodeFn = fn(&mut [MaybeUninit<u8>], &[u8], &Alphabet) -> Result<usize, CodecError>;
+type CompactWhitespaceFn = fn(&mut [u8], &[u8]) -> usize;
+type ValidateFn = fn(&[u8], &Alphabet) -> Result<(), CodecError>;
+type TranscodeFn = fn(&mut [u8], &Alphabet, &Alphabet, &TranscodeTable) -> Result<(), CodecError>;
+
+/// Functions of a backend, taking `(dest, src, ..)`, except `transcode` which works in place
+/// and takes the scalar table of its alphabets, which it may ignore.
+/// All of them work on unpadded base64, and the encoders and decoders only write to `dest`,
+/// which may be uninitialized. They return the length written at its start.
+pub(crate) struct Fns {
+    pub encode: EncodeFn,
+    pub encode_wrapped: EncodeWrappedFn,
+    pub decode: DecodeFn,
+    pub compact_whitespace: CompactWhitespaceFn,
+    pub validate: ValidateFn,
+    pub transcode: TranscodeFn,
+}
+
+static SCALAR: Fns = Fns {
+    encode: |dest, src, alphabet| simple::encode_with_alphabet_uninit(src, dest, alphabet),
+    encode_wrapped: |dest, src, alphabet, wrap| {
+        simple::encode_wrapped_from(src, dest, alphabet, wrap, 0)
+    },
+    decode: |dest, src, alphabet| simple::decode_with_alphabet_uninit(src, dest, alphabet),
+    compact_whitespace: |dest, src| simple::compact_whitespace(src, dest),
+    validate: simple::validate_with_alphabet,
+    transcode: |buf, from, _, table| simple::transcode_with_table(buf, from, table),
+};
+
+static SCALAR_ITER: Fns = Fns {
+    decode: |dest, src, alphabet| simple::decode_iter_with_alphabet_uninit(src, dest, alphabet),
+    ..SCALAR
+};
+
+// Safety: only reachable through `Backend::fns` once ssse3 is known to be available
+#[cfg(target_arch = "x86_64")]
+static SSE: Fns = Fns {
+    encode: |dest, src, alphabet| unsafe { sse::encode_with_alphabet_uninit(dest, src, alphabet) },
+    encode_wrapped: |dest, src, alphabet, wrap| unsafe {
+        sse::encode_wrapped_uninit(dest, src, alphabet, wrap)
+    },
+    decode: |dest, src, alphabet| unsafe { sse::decode_with_alphabet_uninit(dest, src, alphabet) },
+    compact_whitespace: |dest, src| unsafe { sse::compact_whitespace(dest, src) },
+    validate: |src, alphabet| unsafe { sse::validate(src, alphabet) },
+    transcode: |buf, from, to, table| unsafe { sse::transcode_with_table(buf, from, to, table) },
+};
+
+// Safety: only reachable through `Backend::fns` once avx2 is known to be available
+#[cfg(target_arch = "x86_64")]
+static AVX2: Fns = Fns {
+    encode: |dest, src, alphabet| unsafe { avx2::encode_with_alphabet_uninit(dest, src, alphabet) },
+    encode_wrapped: |dest, src, alphabet, wrap| unsafe {
+        avx2::encode_wrapped_uninit(dest, src, alphabet, wrap)
+    },
+    decode: |dest, src, alphabet| unsafe { avx2::decode_with_alphabet_uninit(dest, src, alphabet) },
+    compact_whitespace: |dest, src| unsafe { avx2::compact_whitespace(dest, src) },
+    validate: |src, alphabet| unsafe { avx2::validate(src, alphabet) },
+    transcode: |buf, from, to, _| unsafe { avx2::transcode(buf, from, to) },
+};
+
+/// The detected backend, as its index in `Backend::ALL` plus one, or 0 before detection
+static DETECTED: AtomicU8 = AtomicU8::new(0);
+
+impl Backend {
+    /// Every backend, including those not available on this CPU
+    pub const ALL: &'static [Backend] = &[
+        Backend::Scalar,
+        Backend::ScalarIter,
+        Backend::Sse,
+        Backend::Avx2,
+    ];
+
+    /// The backend used unless the options force one, detected on the first call.
+    ///
+    /// In order of precedence:
+    /// 1. The `backend-scalar`, `backend-sse` or `backend-avx2` feature, at most one of which
+    ///    can be enabled. The SIMD ones need x86_64, and panic if the CPU doesn't support them.
+    /// 2. With the `backend-auto` and `std` features, the backend named by the
+    ///    `BS64_BACKEND` environment variable, ignored if it isn't available.
+    /// 3. The fastest available of [`Backend::Avx2`], [`Backend::Sse`] and [`Backend::Scalar`].
+    pub fn detect() -> Backend {
+        match DETECTED.load(Ordering::Relaxed) {
+            0 => {
+                let backend = Backend::detect_uncached();
+                let index = Backend::ALL.iter().position(|&b| b == backend).unwrap();
+                DETECTED.store(index as u8 + 1, Ordering::Relaxed);
+                backend
+            }
+            index => Backend::ALL[index as usize - 1],
+        }
+    }
+
+    fn detect_uncached() -> Backend {
+        if cfg!(feature = "backend-scalar") {
+            return Backend::Scalar;
+        }
+        if cfg!(feature = "backend-sse") {
+            assert!(
+                Backend::Sse.is_available(),
+                "bs64 was built with backend-sse, but ssse3 is not available"
+            );
+            return Backend::Sse;
+        }
+        if cfg!(feature = "backend-avx2") {
+            assert!(
+                Backend::Avx2.is_available(),
+                "bs64 was built with backend-avx2, but avx2 is not available"
+            );
+            return Backend::Avx2;
+        }
+        #[cfg(all(feature = "backend-auto", feature = "std"))]
+        if let Some(backend) = std::env::var("BS64_BACKEND")
+            .ok()
+            .and_then(|name| Backend::from_name(&name))
+            .filter(|backend| backend.is_available())
+        {
+            return backend;
+        }
+        if Backend::Avx2.is_available() {
+            Backend::Avx2
+        } else if Backend::Sse.is_available() {
+            Backend::Sse
+        } else {
+            Backend::Scalar
+        }
+    }
+
+    /// Whether the backend can run on this CPU, never for the x86_64 backends on other targets
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Scalar | Backend::ScalarIter => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse => sse::has_ssse3(),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => avx2::has_avx2(),
+            #[cfg(not(target_arch = "x86_64"))]
+            Backend::Sse | Backend::Avx2 => false,
+        }
+    }
+
+    /// Name of the backend, as accepted by [`Backend::from_name`] and `BS64_BACKEND`
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Scalar => "scalar",
+            Backend::ScalarIter => "scalar-iter",
+            Backend::Sse => "sse",
+            Backend::Avx2 => "avx2",
+        }
+    }
+
+    /// The backend called `name`, ignoring ASCII case
+    pub fn from_name(name: &str) -> Option<Backend> {
+        Backend::ALL
+            .iter()
+            .copied()
+            .find(|backend| backend.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Functions of the backend, which must be available.
+    /// That's checked by [`Backend::detect`] and the options' `backend` methods,
+    /// so it isn't checked again on every call.
+    pub(crate) fn fns(self) -> &'static Fns {
+        match self {
+            Backend::Scalar => &SCALAR,
+            Backend::ScalarIter => &SCALAR_ITER,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse => &SSE,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => &AVX2,
+            #[cfg(not(target_arch = "x86_64"))]
+            Backend::Sse | Backend::Avx2 => unreachable!("{} is never available", self.name()),
+        }
+    }
+}
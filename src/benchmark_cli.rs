@@ -63,6 +63,60 @@ fn benchmark_decode(num_bytes: usize, iterations: usize) {
     let total = start.elapsed();
     print_performance("bs64::decode_mut()", total, iterations, num_bytes);
 
+    let start = Instant::now();
+    for _ in 0..iterations {
+        bs64::validate(encoded).unwrap();
+    }
+    let total = start.elapsed();
+    print_performance("bs64::validate()", total, iterations, num_bytes);
+
+    let mut output = vec![0u8; encoded.len()];
+    let start = Instant::now();
+    for _ in 0..iterations {
+        bs64::transcode(
+            encoded,
+            &bs64::alphabet::STANDARD,
+            &bs64::alphabet::URL_SAFE,
+            &mut output,
+        )
+        .unwrap();
+    }
+    let total = start.elapsed();
+    print_performance("bs64::transcode()", total, iterations, num_bytes);
+
+    let wrapped = bs64::EncodeOptions::default()
+        .line_wrap(bs64::LineWrap::MIME)
+        .encode(&bytes);
+    let options = bs64::DecodeOptions::default().ignore_whitespace(true);
+    let mut output = vec![0u8; options.decode_len(wrapped.len())];
+    let start = Instant::now();
+    for _ in 0..iterations {
+        options.decode_mut(wrapped.as_bytes(), &mut output).unwrap();
+    }
+    let total = start.elapsed();
+    print_performance("bs64 MIME", total, iterations, num_bytes);
+
+    let mut output = Vec::with_capacity(num_bytes);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        output.clear();
+        let mut reader = bs64::DecoderReader::new(encoded, bs64::DecodeOptions::default());
+        std::io::Read::read_to_end(&mut reader, &mut output).unwrap();
+    }
+    let total = start.elapsed();
+    print_performance("bs64 DecoderReader", total, iterations, num_bytes);
+
+    if bs64::Backend::Sse.is_available() {
+        let options = bs64::DecodeOptions::default().backend(bs64::Backend::Sse);
+        let mut output = vec![0u8; options.decode_len(encoded.len())];
+        let start = Instant::now();
+        for _ in 0..iterations {
+            options.decode_mut(encoded, &mut output).unwrap();
+        }
+        let total = start.elapsed();
+        print_performance("bs64 sse", total, iterations, num_bytes);
+    }
+
     let mut output = vec![0u8; (num_bytes * 4) / 3 + 4];
     let start = Instant::now();
     for _ in 0..iterations {
@@ -135,6 +189,26 @@ fn benchmark_encode(num_bytes: usize, iterations: usize) {
     let total = start.elapsed();
     print_performance("bs64::encode_mut()", total, iterations, num_bytes);
 
+    let options = bs64::EncodeOptions::default().line_wrap(bs64::LineWrap::MIME);
+    let mut output = vec![0u8; options.encode_len(num_bytes)];
+    let start = Instant::now();
+    for _ in 0..iterations {
+        options.encode_mut(&bytes, &mut output).unwrap();
+    }
+    let total = start.elapsed();
+    print_performance("bs64 MIME", total, iterations, num_bytes);
+
+    if bs64::Backend::Sse.is_available() {
+        let options = bs64::EncodeOptions::default().backend(bs64::Backend::Sse);
+        let mut output = vec![0u8; options.encode_len(num_bytes)];
+        let start = Instant::now();
+        for _ in 0..iterations {
+            options.encode_mut(&bytes, &mut output).unwrap();
+        }
+        let total = start.elapsed();
+        print_performance("bs64 sse", total, iterations, num_bytes);
+    }
+
     let mut output = vec![0u8; (num_bytes * 4) / 3 + 4];
     let start = Instant::now();
     for _ in 0..iterations {
@@ -0,0 +1,68 @@
+//! CPU feature detection without std, cached after the first check.
+//!
+//! Features enabled at compile time are never detected, and enabling avx2 also enables ssse3.
+
+use core::arch::x86_64::*;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const UNKNOWN: u8 = 0;
+const MISSING: u8 = 1;
+const PRESENT: u8 = 2;
+
+struct Cached(AtomicU8);
+
+impl Cached {
+    const fn new() -> Self {
+        Cached(AtomicU8::new(UNKNOWN))
+    }
+
+    fn get(&self, detect: impl FnOnce() -> bool) -> bool {
+        match self.0.load(Ordering::Relaxed) {
+            UNKNOWN => {
+                let present = detect();
+                let state = if present { PRESENT } else { MISSING };
+                self.0.store(state, Ordering::Relaxed);
+                present
+            }
+            state => state == PRESENT,
+        }
+    }
+}
+
+pub fn has_avx2() -> bool {
+    static AVX2: Cached = Cached::new();
+    AVX2.get(|| unsafe { cpuid_avx2() })
+}
+
+#[cfg(not(target_feature = "ssse3"))]
+pub fn has_ssse3() -> bool {
+    static SSSE3: Cached = Cached::new();
+    // xmm state is always enabled on x86_64, so only the CPU needs to support it
+    // `__cpuid` is only safe to call on newer toolchains
+    #[allow(unused_unsafe)]
+    SSSE3.get(|| unsafe { __cpuid(1) }.ecx & (1 << 9) != 0)
+}
+
+/// The CPU supports avx2, and the OS saves the ymm registers on context switches
+unsafe fn cpuid_avx2() -> bool {
+    if __cpuid(0).eax < 7 {
+        return false;
+    }
+    // OSXSAVE and AVX
+    let ecx = __cpuid(1).ecx;
+    if ecx & (1 << 27) == 0 || ecx & (1 << 28) == 0 {
+        return false;
+    }
+    // xmm and ymm state enabled in XCR0
+    if xgetbv() & 0b110 != 0b110 {
+        return false;
+    }
+    __cpuid_count(7, 0).ebx & (1 << 5) != 0
+}
+
+/// # Safety
+/// Requires OSXSAVE
+#[target_feature(enable = "xsave")]
+unsafe fn xgetbv() -> u64 {
+    _xgetbv(0)
+}
@@ -0,0 +1,344 @@
+use core::mem::MaybeUninit;
+
+use crate::{as_uninit, data_end, data_end_whitespace, is_whitespace, simple};
+use crate::{encode_len, encode_len_no_pad};
+use crate::{Carry, CodecError, DecodeOptions, EncodeOptions, Trailer};
+
+/// Push style encoder, for input that arrives in fragments.
+///
+/// Up to 2 bytes that don't make up a full group are kept between updates.
+/// The output of all updates and [`Encoder::finalize`] together is the same as
+/// encoding the whole input at once.
+///
+/// ```
+/// use bs64::{EncodeOptions, Encoder};
+///
+/// let mut encoder = Encoder::new(EncodeOptions::default());
+/// let mut output = [0u8; 8];
+/// let mut len = encoder.update(b"hel", &mut output).unwrap();
+/// len += encoder.update(b"lo", &mut output[len..]).unwrap();
+/// len += encoder.finalize(&mut output[len..]).unwrap();
+/// assert_eq!(&output[..len], b"aGVsbG8=");
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Encoder<'a> {
+    options: EncodeOptions<'a>,
+    /// Input bytes that don't make up a full group yet, always < 3
+    partial: [u8; 3],
+    partial_len: usize,
+    /// Chars written to the current line, when wrapping lines
+    line_pos: usize,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(options: EncodeOptions<'a>) -> Self {
+        Encoder {
+            options,
+            partial: [0; 3],
+            partial_len: 0,
+            line_pos: 0,
+        }
+    }
+
+    /// Upper bound on the output of an update with `input_len` bytes
+    pub fn update_len(&self, input_len: usize) -> usize {
+        let chars = (self.partial_len + input_len) / 3 * 4;
+        match self.options.line_wrap {
+            // One extra line ending when the update starts at the end of a line
+            Some(wrap) => wrap.wrapped_len(chars) + wrap.ending().as_bytes().len(),
+            None => chars,
+        }
+    }
+
+    /// Upper bound on the output of [`Encoder::finalize`]
+    pub fn finalize_len(&self) -> usize {
+        let chars = self.options.encode_len(self.partial_len);
+        match self.options.line_wrap {
+            Some(wrap) if chars > 0 => chars + wrap.ending().as_bytes().len(),
+            _ => chars,
+        }
+    }
+
+    /// Encode the full groups of input, keeping any leftover bytes for the next update.
+    /// Returns the number of bytes written to output,
+    /// or an error if output is shorter than `self.update_len(input.len())`.
+    pub fn update(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize, CodecError> {
+        let len = self.update_len(input.len());
+        if output.len() < len {
+            return Err(CodecError::OutputLengthTooShort(output.len(), len));
+        }
+
+        let mut input = input;
+        let mut output_len = 0;
+        if self.partial_len > 0 {
+            let len = (3 - self.partial_len).min(input.len());
+            self.partial[self.partial_len..self.partial_len + len].copy_from_slice(&input[..len]);
+            self.partial_len += len;
+            input = &input[len..];
+            if self.partial_len < 3 {
+                return Ok(0);
+            }
+            let group = self.partial;
+            self.partial_len = 0;
+            output_len = self.encode_groups(&group, output);
+        }
+
+        let (groups, rest) = input.split_at(input.len() / 3 * 3);
+        output_len += self.encode_groups(groups, &mut output[output_len..]);
+        self.partial[..rest.len()].copy_from_slice(rest);
+        self.partial_len = rest.len();
+        Ok(output_len)
+    }
+
+    /// Encode the leftover bytes and padding, and reset the encoder for a new input.
+    /// Returns the number of bytes written to output,
+    /// or an error if output is shorter than `self.finalize_len()`.
+    pub fn finalize(&mut self, output: &mut [u8]) -> Result<usize, CodecError> {
+        let len = self.finalize_len();
+        if output.len() < len {
+            return Err(CodecError::OutputLengthTooShort(output.len(), len));
+        }
+
+        let len = self.partial_len;
+        let mut tail = [0u8; 3];
+        tail[..len].copy_from_slice(&self.partial[..len]);
+        let mut output_len = self.encode_groups(&tail[..len], output);
+        if len > 0 && self.options.padding {
+            let pad_len = encode_len(len) - encode_len_no_pad(len);
+            output[output_len..output_len + pad_len].fill(b'=');
+            output_len += pad_len;
+        }
+        *self = Encoder::new(self.options);
+        Ok(output_len)
+    }
+
+    /// Encode input without padding.
+    /// Input is full groups, unless it's the final group.
+    fn encode_groups(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        let alphabet = self.options.alphabet;
+        let Some(wrap) = self.options.line_wrap else {
+            // Safety: encoding only writes chars to output
+            return (self.options.fns().encode)(unsafe { as_uninit(output) }, input, alphabet);
+        };
+
+        // Finish the current line, then wrap the rest from the start of a line
+        let line_len = wrap.line_len();
+        let head_len = if self.line_pos > 0 {
+            ((line_len - self.line_pos) / 4 * 3).min(input.len())
+        } else {
+            0
+        };
+        let (head, rest) = input.split_at(head_len);
+        // Safety: encoding only writes chars to output, which holds the update length
+        let mut len =
+            simple::encode_with_alphabet_uninit(head, unsafe { as_uninit(output) }, alphabet);
+        self.line_pos += len;
+        if !rest.is_empty() {
+            if self.line_pos > 0 {
+                let ending = wrap.ending().as_bytes();
+                output[len..len + ending.len()].copy_from_slice(ending);
+                len += ending.len();
+            }
+            // Safety: encoding only writes chars and line endings to output
+            let output = unsafe { as_uninit(&mut output[len..]) };
+            len += (self.options.fns().encode_wrapped)(output, rest, alphabet, wrap);
+            let chars = encode_len_no_pad(rest.len());
+            self.line_pos = (chars - 1) % line_len + 1;
+        }
+        len
+    }
+}
+
+/// Push style decoder, for input that arrives in fragments.
+///
+/// Up to 3 chars that don't make up a full group are kept between updates,
+/// and padding is checked by [`Decoder::finalize`].
+/// The output of all updates and `finalize` together is the same as decoding the
+/// whole input at once, and errors have offsets into the whole input.
+///
+/// An input is rejected exactly when [`DecodeOptions::decode`] rejects it, but the error
+/// can differ if the input has several problems: invalid chars are reported by the update
+/// that reads them, while `decode` checks the length and padding of the input first.
+/// E.g. `"\n"` fails with [`CodecError::InvalidByte`] here and with
+/// [`CodecError::InputModError`] from `decode`.
+///
+/// ```
+/// use bs64::{DecodeOptions, Decoder};
+///
+/// let mut decoder = Decoder::new(DecodeOptions::default());
+/// let mut output = [0u8; 8];
+/// let mut len = decoder.update(b"aGVsb", &mut output).unwrap();
+/// len += decoder.update(b"G8=", &mut output[len..]).unwrap();
+/// len += decoder.finalize(&mut output[len..]).unwrap();
+/// assert_eq!(&output[..len], b"hello");
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Decoder<'a> {
+    options: DecodeOptions<'a>,
+    carry: Carry,
+    /// Chars consumed by previous updates
+    offset: usize,
+    /// Chars consumed so far, without whitespace and padding
+    data_len: usize,
+    /// Offset of the last char that isn't whitespace or padding
+    last_offset: usize,
+    /// Offset of the first padding char, once padding started
+    pad_offset: Option<usize>,
+    pad_len: usize,
+    /// Offset of the end of the last char that isn't whitespace
+    end: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(options: DecodeOptions<'a>) -> Self {
+        Decoder {
+            options,
+            carry: Carry::default(),
+            offset: 0,
+            data_len: 0,
+            last_offset: 0,
+            pad_offset: None,
+            pad_len: 0,
+            end: 0,
+        }
+    }
+
+    /// Upper bound on the output of an update with `input_len` chars.
+    /// An update only needs room for the chars it decodes, without padding and whitespace.
+    pub fn update_len(&self, input_len: usize) -> usize {
+        (self.carry.len + input_len) / 4 * 3
+    }
+
+    /// Upper bound on the output of [`Decoder::finalize`]
+    pub fn finalize_len(&self) -> usize {
+        crate::decode_len_no_pad(self.carry.len)
+    }
+
+    /// Decode the full groups of input, keeping any leftover chars for the next update.
+    /// Returns the number of bytes written to output.
+    pub fn update(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize, CodecError> {
+        // Padding inside the data is rejected by the backend as an invalid char,
+        // at the same offset as padding followed by more data below
+        let data_len = match self.pad_offset {
+            Some(_) => 0,
+            None if self.options.ignore_whitespace => data_end_whitespace(input),
+            None => data_end(input),
+        };
+        let (data, trailer) = input.split_at(data_len);
+        let chars = match self.options.ignore_whitespace {
+            true => data.iter().filter(|&&c| !is_whitespace(c)).count(),
+            false => data.len(),
+        };
+        let len = self.update_len(chars);
+        if output.len() < len {
+            return Err(CodecError::OutputLengthTooShort(output.len(), len));
+        }
+        // Safety: decoding only writes bytes to output
+        let output = unsafe { as_uninit(output) };
+        let output_len = if self.options.ignore_whitespace {
+            let (output_len, data_len) = self
+                .options
+                .decode_whitespace_groups(data, &mut self.carry, output)
+                .map_err(|e| e.offset_by(self.offset))?;
+            self.data_len += data_len;
+            if let Some(i) = data.iter().rposition(|&c| !is_whitespace(c)) {
+                self.last_offset = self.offset + i;
+                self.end = self.last_offset + 1;
+            }
+            output_len
+        } else {
+            let output_len = self.decode_groups(data, output)?;
+            self.data_len += data.len();
+            if !data.is_empty() {
+                self.last_offset = self.offset + data.len() - 1;
+                self.end = self.last_offset + 1;
+            }
+            output_len
+        };
+        self.offset += data.len();
+
+        for (i, &c) in trailer.iter().enumerate() {
+            if c == b'=' {
+                self.pad_offset.get_or_insert(self.offset + i);
+                self.pad_len += 1;
+                self.end = self.offset + i + 1;
+            } else if !(self.options.ignore_whitespace && is_whitespace(c)) {
+                // Padding followed by more data, so the padding is the invalid part
+                return Err(CodecError::InvalidByte {
+                    offset: self.pad_offset.unwrap_or(self.offset + i),
+                    byte: b'=',
+                });
+            }
+        }
+        self.offset += trailer.len();
+        Ok(output_len)
+    }
+
+    /// Decode the leftover chars, check the padding, and reset the decoder for a new input.
+    /// Returns the number of bytes written to output.
+    pub fn finalize(&mut self, output: &mut [u8]) -> Result<usize, CodecError> {
+        let len = self.finalize_len();
+        if output.len() < len {
+            return Err(CodecError::OutputLengthTooShort(output.len(), len));
+        }
+        // Safety: decoding only writes bytes to output
+        let output = unsafe { as_uninit(output) };
+
+        let data_end = match self.data_len {
+            0 => 0,
+            _ => self.last_offset + 1,
+        };
+        let trailer = Trailer {
+            pad_offset: self.pad_offset.unwrap_or(data_end),
+            pad_len: self.pad_len,
+            end: self.end,
+        };
+        self.options.check_padding(self.data_len, trailer)?;
+        let output_len =
+            (self.options.fns().decode)(output, self.carry.chars(), self.options.alphabet)?;
+        self.options
+            .check_trailing_bits(self.carry.chars(), self.data_len)
+            .map_err(|_| CodecError::TrailingBits(self.last_offset))?;
+        *self = Decoder::new(self.options);
+        Ok(output_len)
+    }
+
+    /// Decode the full groups of the carried chars followed by data, without whitespace
+    fn decode_groups(
+        &mut self,
+        data: &[u8],
+        output: &mut [MaybeUninit<u8>],
+    ) -> Result<usize, CodecError> {
+        let alphabet = self.options.alphabet;
+        let decode = self.options.fns().decode;
+        let mut output_len = 0;
+        let head_len = ((4 - self.carry.len) % 4).min(data.len());
+        let (head, data) = data.split_at(head_len);
+        self.push_carry(head, self.offset)?;
+        if self.carry.len == 4 {
+            output_len = decode(output, self.carry.chars(), alphabet)?;
+            self.carry.len = 0;
+        }
+
+        let (groups, rest) = data.split_at(data.len() / 4 * 4);
+        output_len += decode(&mut output[output_len..], groups, alphabet)
+            .map_err(|e| e.offset_by(self.offset + head_len))?;
+        self.push_carry(rest, self.offset + head_len + groups.len())?;
+        Ok(output_len)
+    }
+
+    /// Add chars starting at `offset` in the input to the carry, checking they're valid
+    fn push_carry(&mut self, chars: &[u8], offset: usize) -> Result<(), CodecError> {
+        for (i, &c) in chars.iter().enumerate() {
+            if self.options.alphabet.value(c).is_none() {
+                return Err(CodecError::InvalidByte {
+                    offset: offset + i,
+                    byte: c,
+                });
+            }
+            self.carry.chars[self.carry.len] = c;
+            self.carry.len += 1;
+        }
+        Ok(())
+    }
+}
@@ -1,91 +1,1171 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::mem::MaybeUninit;
+use simple::TranscodeTable;
 use thiserror::Error;
 
+pub mod alphabet;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_io;
+#[cfg(target_arch = "x86_64")]
 pub mod avx2;
+mod backend;
+#[cfg(all(
+    target_arch = "x86_64",
+    not(feature = "std"),
+    not(target_feature = "avx2")
+))]
+mod detect;
+mod incremental;
+#[cfg(feature = "std")]
+mod read;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod simple;
+#[cfg(target_arch = "x86_64")]
+pub mod sse;
+#[cfg(feature = "std")]
+mod write;
+
+pub use alphabet::Alphabet;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use async_io::{AsyncDecoderReader, AsyncEncoderWriter};
+pub use backend::Backend;
+pub use incremental::{Decoder, Encoder};
+#[cfg(feature = "std")]
+pub use read::DecoderReader;
+#[cfg(feature = "std")]
+pub use write::EncoderWriter;
 
 /// The error type for encoding and decoding.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum CodecError {
+    #[cfg(feature = "std")]
     #[error("codec error")]
     CodecError(#[from] std::io::Error),
     #[error("output length {0} is < expected length {1}")]
     OutputLengthTooShort(usize, usize),
-    #[error("input length {0} != 0 % 4")]
+    /// The data ends with a single char, which can't be decoded on its own.
+    /// Holds the offset in the input where that truncated group ends.
+    #[error("truncated base64 group ending at offset {0}")]
     InputModError(usize),
+    #[cfg(feature = "alloc")]
+    #[deprecated(note = "decode errors are reported as `InvalidByte`")]
     #[error("invalid input: {0}")]
     InvalidInput(String),
+    #[error("invalid byte {byte:#04x} at offset {offset}")]
+    InvalidByte { offset: usize, byte: u8 },
+    #[error("invalid padding at offset {0}")]
+    InvalidPadding(usize),
+    #[error("non-zero trailing bits in char at offset {0}")]
+    TrailingBits(usize),
+    #[error("alphabet char {1:#04x} at index {0} is not ASCII, is repeated or is reserved")]
+    InvalidAlphabet(usize, u8),
+    #[error("backend {} is not available on this CPU", .0.name())]
+    UnavailableBackend(Backend),
     #[error("unknown codec error")]
     Unknown,
 }
 
-#[derive(Default)]
-pub struct EncodeOptions {}
+impl CodecError {
+    /// Shift the offset of an error found in a sub-slice starting at `offset` in the input
+    pub(crate) fn offset_by(self, offset: usize) -> Self {
+        match self {
+            CodecError::InvalidByte { offset: i, byte } => CodecError::InvalidByte {
+                offset: offset + i,
+                byte,
+            },
+            CodecError::InvalidPadding(i) => CodecError::InvalidPadding(offset + i),
+            CodecError::TrailingBits(i) => CodecError::TrailingBits(offset + i),
+            CodecError::InputModError(i) => CodecError::InputModError(offset + i),
+            other => other,
+        }
+    }
+
+    /// Copy of the error, to report it again. An `io::Error` is copied with its kind and
+    /// message only, decode errors never wrap one.
+    #[cfg(feature = "std")]
+    #[allow(deprecated)]
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            CodecError::CodecError(e) => std::io::Error::new(e.kind(), e.to_string()).into(),
+            CodecError::OutputLengthTooShort(len, expected) => {
+                CodecError::OutputLengthTooShort(*len, *expected)
+            }
+            CodecError::InputModError(i) => CodecError::InputModError(*i),
+            CodecError::InvalidInput(s) => CodecError::InvalidInput(s.clone()),
+            CodecError::InvalidByte { offset, byte } => CodecError::InvalidByte {
+                offset: *offset,
+                byte: *byte,
+            },
+            CodecError::InvalidPadding(i) => CodecError::InvalidPadding(*i),
+            CodecError::TrailingBits(i) => CodecError::TrailingBits(*i),
+            CodecError::InvalidAlphabet(i, c) => CodecError::InvalidAlphabet(*i, *c),
+            CodecError::UnavailableBackend(backend) => CodecError::UnavailableBackend(*backend),
+            CodecError::Unknown => CodecError::Unknown,
+        }
+    }
+}
+
+/// Decode errors become [`std::io::ErrorKind::InvalidData`] errors
+#[cfg(feature = "std")]
+impl From<CodecError> for std::io::Error {
+    fn from(e: CodecError) -> Self {
+        match e {
+            CodecError::CodecError(e) => e,
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// Line ending inserted between lines of wrapped output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
 
-pub fn encode_len(input_len: usize) -> usize {
+impl LineEnding {
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+}
+
+/// Line wrapping for encoded output.
+/// Line endings are placed between lines, there is no line ending after the last line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineWrap {
+    line_len: usize,
+    ending: LineEnding,
+}
+
+impl LineWrap {
+    /// 76 char lines with CRLF endings, as used in MIME (RFC 2045)
+    pub const MIME: LineWrap = LineWrap::new(76, LineEnding::CrLf);
+    /// 64 char lines with LF endings, as used in PEM (RFC 7468)
+    pub const PEM: LineWrap = LineWrap::new(64, LineEnding::Lf);
+
+    /// # Panics
+    /// If `line_len` is 0 or not a multiple of 4
+    pub const fn new(line_len: usize, ending: LineEnding) -> Self {
+        assert!(
+            line_len > 0 && line_len.is_multiple_of(4),
+            "line length must be a non-zero multiple of 4"
+        );
+        LineWrap { line_len, ending }
+    }
+
+    pub fn line_len(&self) -> usize {
+        self.line_len
+    }
+
+    pub fn ending(&self) -> LineEnding {
+        self.ending
+    }
+
+    /// Length of `encoded_len` chars of base64 once line endings are added
+    pub fn wrapped_len(&self, encoded_len: usize) -> usize {
+        let breaks = encoded_len.saturating_sub(1) / self.line_len;
+        encoded_len + breaks * self.ending.as_bytes().len()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EncodeOptions<'a> {
+    alphabet: &'a Alphabet,
+    padding: bool,
+    line_wrap: Option<LineWrap>,
+    backend: Option<Backend>,
+}
+
+impl Default for EncodeOptions<'_> {
+    fn default() -> Self {
+        EncodeOptions {
+            alphabet: &alphabet::STANDARD,
+            padding: true,
+            line_wrap: None,
+            backend: None,
+        }
+    }
+}
+
+/// Length of the padded base64 encoding of `input_len` bytes
+pub const fn encode_len(input_len: usize) -> usize {
     match input_len % 3 {
         0 => input_len / 3 * 4,
         _ => input_len / 3 * 4 + 4,
     }
 }
 
-pub fn decode_len(input_len: usize) -> usize {
+/// Length of the unpadded base64 encoding of `input_len` bytes
+pub const fn encode_len_no_pad(input_len: usize) -> usize {
+    match input_len % 3 {
+        0 => input_len / 3 * 4,
+        rem => input_len / 3 * 4 + rem + 1,
+    }
+}
+
+/// Upper bound on the decoded length of `input_len` chars of padded base64
+pub const fn decode_len(input_len: usize) -> usize {
     (input_len / 4) * 3
 }
 
-impl EncodeOptions {
+/// Upper bound on the decoded length of `input_len` chars of unpadded base64
+pub const fn decode_len_no_pad(input_len: usize) -> usize {
+    (input_len / 4) * 3 + (input_len % 4) * 3 / 4
+}
+
+impl<'a> EncodeOptions<'a> {
+    /// Set the alphabet used for encoding, e.g. [`alphabet::URL_SAFE`]
+    pub fn alphabet(mut self, alphabet: &'a Alphabet) -> Self {
+        self.alphabet = alphabet;
+        self
+    }
+
+    /// Set whether the output is padded with `=` to a multiple of 4 chars (default: true)
+    pub fn padding(mut self, padding: bool) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Break the output into lines, e.g. [`LineWrap::MIME`] (default: no line breaks)
+    pub fn line_wrap(mut self, line_wrap: LineWrap) -> Self {
+        self.line_wrap = Some(line_wrap);
+        self
+    }
+
+    /// Use `backend` instead of the one picked by [`Backend::detect`].
+    /// Unlike `BS64_BACKEND`, an unavailable backend isn't replaced by the detected one,
+    /// see [`Self::try_backend`] to handle it.
+    ///
+    /// # Panics
+    /// If `backend` isn't available on this CPU
+    pub fn backend(self, backend: Backend) -> Self {
+        self.try_backend(backend).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like [`Self::backend`], failing with [`CodecError::UnavailableBackend`]
+    /// if `backend` isn't available on this CPU
+    pub fn try_backend(mut self, backend: Backend) -> Result<Self, CodecError> {
+        if !backend.is_available() {
+            return Err(CodecError::UnavailableBackend(backend));
+        }
+        self.backend = Some(backend);
+        Ok(self)
+    }
+
+    /// Functions of the forced or detected backend
+    pub(crate) fn fns(&self) -> &'static backend::Fns {
+        self.backend.unwrap_or_else(Backend::detect).fns()
+    }
+
+    /// Length of the encoding of `input_len` bytes with these options
+    pub fn encode_len(&self, input_len: usize) -> usize {
+        let len = if self.padding {
+            encode_len(input_len)
+        } else {
+            encode_len_no_pad(input_len)
+        };
+        match self.line_wrap {
+            Some(wrap) => wrap.wrapped_len(len),
+            None => len,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
     pub fn encode(self, input: &[u8]) -> String {
-        let mut output = vec![0u8; encode_len(input.len())];
-        avx2::encode_with_fallback(&mut output, input);
-        unsafe { String::from_utf8_unchecked(output) }
+        let mut output = String::new();
+        self.encode_to_string(input, &mut output);
+        output
+    }
+
+    /// Append the encoding of `input` to `output`, growing it only if its spare capacity
+    /// is too small. Returns the appended length.
+    #[cfg(feature = "alloc")]
+    pub fn encode_to_string(self, input: &[u8], output: &mut String) -> usize {
+        // Safety: alphabets, padding and line endings are ASCII, so output stays UTF-8
+        let output = unsafe { output.as_mut_vec() };
+        output.reserve(self.encode_len(input.len()));
+        // The spare capacity holds the encoded length
+        let len = self.encode_unchecked(input, output.spare_capacity_mut());
+        // Safety: the first len bytes of the spare capacity were written
+        unsafe { output.set_len(output.len() + len) };
+        len
     }
 
     pub fn encode_mut(self, input: &[u8], output: &mut [u8]) -> Result<usize, CodecError> {
-        if output.len() < encode_len(input.len()) {
-            Err(CodecError::OutputLengthTooShort(
-                output.len(),
-                encode_len(input.len()),
-            ))
+        // Safety: encoding only writes chars to output
+        let output = unsafe { as_uninit(output) };
+        Ok(self.encode_uninit(input, output)?.len())
+    }
+
+    /// Like [`EncodeOptions::encode_mut`], with an output that doesn't need to be initialized.
+    /// Returns the encoded part of `output`.
+    pub fn encode_uninit<'o>(
+        self,
+        input: &[u8],
+        output: &'o mut [MaybeUninit<u8>],
+    ) -> Result<&'o mut [u8], CodecError> {
+        let len = self.encode_len(input.len());
+        if output.len() < len {
+            return Err(CodecError::OutputLengthTooShort(output.len(), len));
+        }
+        let len = self.encode_unchecked(input, output);
+        // Safety: the first len bytes were written
+        Ok(unsafe { assume_init(&mut output[..len]) })
+    }
+
+    /// Encode the first `data_len` bytes of `buf` in place, returning the encoded length.
+    /// `buf` must hold at least `self.encode_len(data_len)` bytes.
+    pub fn encode_in_place(self, buf: &mut [u8], data_len: usize) -> Result<usize, CodecError> {
+        let len = self.encode_len(data_len);
+        if buf.len() < len {
+            return Err(CodecError::OutputLengthTooShort(buf.len(), len));
+        }
+
+        let encoded_len = encode_len_no_pad(data_len);
+        self.encode_data_in_place(buf, data_len);
+        let padded_len = if self.padding {
+            buf[encoded_len..encode_len(data_len)].fill(b'=');
+            encode_len(data_len)
         } else {
-            Ok(avx2::encode_with_fallback(output, input))
+            encoded_len
+        };
+        if let Some(wrap) = self.line_wrap {
+            wrap_in_place(buf, padded_len, wrap);
+        }
+        Ok(len)
+    }
+
+    /// Encode `buf[..data_len]` in place without padding, from the last block to the first.
+    ///
+    /// The output of a block starts after the block once it's at most a third of the input
+    /// before it, so blocks shrink towards the start and are passed to the backend as
+    /// separate slices. The first block is encoded into a stack buffer instead.
+    fn encode_data_in_place(self, buf: &mut [u8], data_len: usize) {
+        let fns = self.fns();
+        let mut block_end = data_len;
+        while block_end > IN_PLACE_FIRST_BLOCK_LEN {
+            // A multiple of 3, so only the last block has a partial group
+            let block_start = (block_end * 3).div_ceil(4).next_multiple_of(3);
+            let (src, dest) = buf.split_at_mut(block_end);
+            let dest_start = encode_len_no_pad(block_start) - block_end;
+            // Safety: encoding only writes chars to dest
+            let dest = unsafe { as_uninit(&mut dest[dest_start..]) };
+            (fns.encode)(dest, &src[block_start..], self.alphabet);
+            block_end = block_start;
+        }
+
+        let mut first = [MaybeUninit::uninit(); encode_len_no_pad(IN_PLACE_FIRST_BLOCK_LEN)];
+        let len = (fns.encode)(&mut first, &buf[..block_end], self.alphabet);
+        // Safety: the first len bytes were written
+        let first = unsafe { assume_init(&mut first[..len]) };
+        buf[..len].copy_from_slice(first);
+    }
+
+    /// Finish `data_len` chars transcoded to this alphabet at the start of `output`,
+    /// which must be at least `self.encode_len(decode_len_no_pad(data_len))` long.
+    /// Clears the unused bits of the last char, like encoding the decoded data would,
+    /// and adds padding and line endings. Returns the length of the output.
+    fn finish_transcode(self, output: &mut [u8], data_len: usize) -> usize {
+        if let Some(last) = output[..data_len].last_mut() {
+            let value = self.alphabet.value(*last).unwrap_or_default();
+            *last = self.alphabet.chars[(value & !unused_bits(data_len)) as usize];
+        }
+        let padded_len = if self.padding {
+            let padded_len = data_len.next_multiple_of(4);
+            output[data_len..padded_len].fill(b'=');
+            padded_len
+        } else {
+            data_len
+        };
+        if let Some(wrap) = self.line_wrap {
+            wrap_in_place(output, padded_len, wrap);
+        }
+        self.encode_len(decode_len_no_pad(data_len))
+    }
+
+    /// Encode into output, which must be at least `self.encode_len(input.len())` long.
+    /// Returns the encoded length, all of which is written
+    fn encode_unchecked(self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> usize {
+        let len = match self.line_wrap {
+            Some(wrap) => (self.fns().encode_wrapped)(output, input, self.alphabet, wrap),
+            None => (self.fns().encode)(output, input, self.alphabet),
+        };
+        if self.padding {
+            // The last group always fits on the last line
+            let pad_len = encode_len(input.len()) - encode_len_no_pad(input.len());
+            output[len..len + pad_len].fill(MaybeUninit::new(b'='));
+            len + pad_len
+        } else {
+            len
         }
     }
 }
 
-#[derive(Default)]
-pub struct DecodeOptions {}
+/// How padding is handled when decoding
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Padding {
+    /// Input must be padded to a multiple of 4 chars
+    #[default]
+    Required,
+    /// Input may be padded, but if it is the padding must be complete
+    Optional,
+    /// Input must not contain padding
+    Forbidden,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DecodeOptions<'a> {
+    alphabet: &'a Alphabet,
+    padding: Padding,
+    ignore_whitespace: bool,
+    canonical: bool,
+    backend: Option<Backend>,
+}
+
+impl Default for DecodeOptions<'_> {
+    fn default() -> Self {
+        DecodeOptions {
+            alphabet: &alphabet::STANDARD,
+            padding: Padding::Required,
+            ignore_whitespace: false,
+            canonical: false,
+            backend: None,
+        }
+    }
+}
+
+/// Chars left over after the last full group, always < 4
+#[derive(Clone, Copy, Debug, Default)]
+struct Carry {
+    chars: [u8; 4],
+    len: usize,
+}
+
+impl Carry {
+    fn chars(&self) -> &[u8] {
+        &self.chars[..self.len]
+    }
+}
+
+/// Padding after the data of an input, with offsets into the input
+#[derive(Clone, Copy, Debug, Default)]
+struct Trailer {
+    /// Offset of the first padding char, or of the end of the data without padding
+    pad_offset: usize,
+    /// Number of padding chars
+    pad_len: usize,
+    /// Offset of the end of the last padding char, or of the data without padding
+    end: usize,
+}
+
+/// Whitespace skipped by [`DecodeOptions::ignore_whitespace`]
+pub(crate) fn is_whitespace(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+/// Number of whitespace chars in data, counted in u8 lanes so it vectorizes
+pub(crate) fn count_whitespace(data: &[u8]) -> usize {
+    data.chunks(255)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|&c| ((c == b' ') | (c == b'\t') | (c == b'\r') | (c == b'\n')) as u8)
+                .sum::<u8>() as usize
+        })
+        .sum()
+}
+
+/// Size of the stack buffer whitespace is compacted into before decoding
+const WHITESPACE_BLOCK_LEN: usize = 4096;
+
+/// Chars copied to the output at a time before they're transcoded there
+const TRANSCODE_BLOCK_LEN: usize = 4096;
+
+/// Input length of the first block encoded or decoded in place,
+/// which goes through a stack buffer
+const IN_PLACE_FIRST_BLOCK_LEN: usize = 3072;
+
+impl<'a> DecodeOptions<'a> {
+    /// Set the alphabet used for decoding, e.g. [`alphabet::URL_SAFE`]
+    pub fn alphabet(mut self, alphabet: &'a Alphabet) -> Self {
+        self.alphabet = alphabet;
+        self
+    }
+
+    /// Set how padding in the input is handled (default: [`Padding::Required`])
+    pub fn padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Upper bound on the decoded length of `input_len` chars with these options
+    pub fn decode_len(&self, input_len: usize) -> usize {
+        match self.padding {
+            Padding::Required => decode_len(input_len),
+            Padding::Optional | Padding::Forbidden => decode_len_no_pad(input_len),
+        }
+    }
+
+    /// Skip spaces, tabs, CR and LF anywhere in the input (default: false)
+    pub fn ignore_whitespace(mut self, ignore_whitespace: bool) -> Self {
+        self.ignore_whitespace = ignore_whitespace;
+        self
+    }
+
+    /// Only accept the canonical encoding of the data, by rejecting non-zero bits left over
+    /// in the last char (default: false).
+    ///
+    /// Every output has exactly one accepted encoding, so [`Padding::Optional`] is handled
+    /// like [`Padding::Required`] in canonical mode, and unpadded input fails with
+    /// [`CodecError::InvalidPadding`].
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// Use `backend` instead of the one picked by [`Backend::detect`].
+    /// Unlike `BS64_BACKEND`, an unavailable backend isn't replaced by the detected one,
+    /// see [`Self::try_backend`] to handle it.
+    ///
+    /// # Panics
+    /// If `backend` isn't available on this CPU
+    pub fn backend(self, backend: Backend) -> Self {
+        self.try_backend(backend).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like [`Self::backend`], failing with [`CodecError::UnavailableBackend`]
+    /// if `backend` isn't available on this CPU
+    pub fn try_backend(mut self, backend: Backend) -> Result<Self, CodecError> {
+        if !backend.is_available() {
+            return Err(CodecError::UnavailableBackend(backend));
+        }
+        self.backend = Some(backend);
+        Ok(self)
+    }
+
+    /// Functions of the forced or detected backend
+    pub(crate) fn fns(&self) -> &'static backend::Fns {
+        self.backend.unwrap_or_else(Backend::detect).fns()
+    }
 
-impl DecodeOptions {
+    #[cfg(feature = "alloc")]
     pub fn decode(self, input: &[u8]) -> Result<Vec<u8>, CodecError> {
-        let mut output = vec![0u8; decode_len(input.len())];
-        let decode_len = avx2::decode_with_fallback(&mut output, &input)?;
-        output.truncate(decode_len);
+        let mut output = Vec::new();
+        self.decode_to_vec(input, &mut output)?;
         Ok(output)
     }
 
+    /// Append the decoding of `input` to `output`, growing it only if its spare capacity
+    /// is too small. Returns the appended length, `output` is unchanged on error.
+    #[cfg(feature = "alloc")]
+    pub fn decode_to_vec(self, input: &[u8], output: &mut Vec<u8>) -> Result<usize, CodecError> {
+        output.reserve(self.decode_len(input.len()));
+        // The spare capacity holds the decoded length
+        let len = self.decode_unchecked(input, output.spare_capacity_mut())?;
+        // Safety: the first len bytes of the spare capacity were written
+        unsafe { output.set_len(output.len() + len) };
+        Ok(len)
+    }
+
+    /// Exact decoded length of `input` with these options.
+    ///
+    /// Fails if the length or padding of `input` is invalid, the chars themselves aren't checked.
+    pub fn decoded_len(&self, input: &[u8]) -> Result<usize, CodecError> {
+        let (_, data_len, trailer) = self.split_padding(input);
+        self.check_padding(data_len, trailer)?;
+        Ok(decode_len_no_pad(data_len))
+    }
+
+    /// Where the data of `input` ends, its number of chars without whitespace,
+    /// and the padding after it
+    fn split_padding(&self, input: &[u8]) -> (usize, usize, Trailer) {
+        let (data_end, trailer) = self.split_trailer(input);
+        let data = &input[..data_end];
+        let data_len = match self.ignore_whitespace {
+            true => data.len() - count_whitespace(data),
+            false => data.len(),
+        };
+        (data_end, data_len, trailer)
+    }
+
+    /// Where the data of `input` ends, and the padding after it
+    fn split_trailer(&self, input: &[u8]) -> (usize, Trailer) {
+        if !self.ignore_whitespace {
+            let data_end = data_end(input);
+            let trailer = Trailer {
+                pad_offset: data_end,
+                pad_len: input.len() - data_end,
+                end: input.len(),
+            };
+            return (data_end, trailer);
+        }
+
+        let data_end = data_end_whitespace(input);
+        let rest = &input[data_end..];
+        let trailer = Trailer {
+            pad_offset: data_end + rest.iter().position(|&c| c == b'=').unwrap_or(0),
+            pad_len: rest.iter().filter(|&&c| c == b'=').count(),
+            end: data_end + rest.iter().rposition(|&c| c == b'=').map_or(0, |i| i + 1),
+        };
+        (data_end, trailer)
+    }
+
     pub fn decode_mut(self, input: &[u8], output: &mut [u8]) -> Result<usize, CodecError> {
-        if output.len() < decode_len(input.len()) {
-            Err(CodecError::OutputLengthTooShort(
-                output.len(),
-                decode_len(input.len()),
-            ))
+        // Safety: decoding only writes bytes to output
+        let output = unsafe { as_uninit(output) };
+        Ok(self.decode_uninit(input, output)?.len())
+    }
+
+    /// Like [`DecodeOptions::decode_mut`], with an output that doesn't need to be initialized.
+    /// Returns the decoded part of `output`.
+    pub fn decode_uninit<'o>(
+        self,
+        input: &[u8],
+        output: &'o mut [MaybeUninit<u8>],
+    ) -> Result<&'o mut [u8], CodecError> {
+        // The exact length needs a pass over the input when skipping whitespace,
+        // which isn't needed when the output is larger than the upper bound
+        if output.len() < self.decode_len(input.len()) {
+            let len = self.decoded_len(input)?;
+            if output.len() < len {
+                return Err(CodecError::OutputLengthTooShort(output.len(), len));
+            }
+        }
+        let len = self.decode_unchecked(input, output)?;
+        // Safety: the first len bytes were written
+        Ok(unsafe { assume_init(&mut output[..len]) })
+    }
+
+    /// Check that `input` would decode without error, without producing any output
+    pub fn validate(&self, input: &[u8]) -> Result<(), CodecError> {
+        if self.ignore_whitespace {
+            return self.validate_whitespace(input);
+        }
+
+        let (data_len, trailer) = self.split_trailer(input);
+        self.check_padding(data_len, trailer)?;
+        (self.fns().validate)(&input[..data_len], self.alphabet)?;
+        self.check_trailing_bits(&input[..data_len], data_len)
+    }
+
+    /// Validate input containing whitespace, by compacting it into blocks on the stack.
+    /// Errors are checked in the same order as [`DecodeOptions::decode_whitespace`].
+    fn validate_whitespace(&self, input: &[u8]) -> Result<(), CodecError> {
+        let (data_end, trailer) = self.split_trailer(input);
+        let data = &input[..data_end];
+
+        let mut buf = [0u8; WHITESPACE_BLOCK_LEN];
+        let mut data_len = 0;
+        let fns = self.fns();
+        for (i, block) in data.chunks(WHITESPACE_BLOCK_LEN).enumerate() {
+            let len = (fns.compact_whitespace)(&mut buf, block);
+            (fns.validate)(&buf[..len], self.alphabet)
+                .map_err(|e| uncompact_offset(e, block, 0).offset_by(i * WHITESPACE_BLOCK_LEN))?;
+            data_len += len;
+        }
+        self.check_padding(data_len, trailer)?;
+        self.check_trailing_bits(data, data_len)
+    }
+
+    /// Decode `buf` in place, returning the decoded part at its start.
+    /// The rest of `buf`, and all of it on error, is left with unspecified contents.
+    pub fn decode_in_place(self, buf: &mut [u8]) -> Result<&mut [u8], CodecError> {
+        let len = if self.ignore_whitespace {
+            self.decode_whitespace_in_place(buf)?
+        } else {
+            let (data_len, trailer) = self.split_trailer(buf);
+            self.check_padding(data_len, trailer)?;
+            let output_len = self.decode_data_in_place(&mut buf[..data_len])?;
+            // n >= 2 chars decode to at most n - 1 bytes, so the last char is still there
+            self.check_trailing_bits(&buf[..data_len], data_len)?;
+            output_len
+        };
+        Ok(&mut buf[..len])
+    }
+
+    /// Decode unpadded `data` in place.
+    ///
+    /// The output of a block ends before the block starts once a third of the input is
+    /// decoded, so blocks grow with the decoded part and are passed to the backend as
+    /// separate slices. The first block is decoded into a stack buffer instead.
+    fn decode_data_in_place(self, data: &mut [u8]) -> Result<usize, CodecError> {
+        let fns = self.fns();
+        let first_len = data.len().min(IN_PLACE_FIRST_BLOCK_LEN);
+        let mut first = [MaybeUninit::uninit(); decode_len_no_pad(IN_PLACE_FIRST_BLOCK_LEN)];
+        let mut dest_i = (fns.decode)(&mut first, &data[..first_len], self.alphabet)?;
+        // Safety: the first dest_i bytes were written
+        data[..dest_i].copy_from_slice(unsafe { assume_init(&mut first[..dest_i]) });
+
+        let mut src_i = first_len;
+        while src_i < data.len() {
+            // A multiple of 4, so only the last block has a partial group
+            let block_len = (src_i / 3 / 4 * 4).min(data.len() - src_i);
+            let (dest, src) = data.split_at_mut(src_i);
+            // Safety: decoding only writes bytes to dest
+            let dest = unsafe { as_uninit(&mut dest[dest_i..]) };
+            dest_i += (fns.decode)(dest, &src[..block_len], self.alphabet)
+                .map_err(|e| e.offset_by(src_i))?;
+            src_i += block_len;
+        }
+        Ok(dest_i)
+    }
+
+    /// Decode `buf` containing whitespace in place, block by block through a stack buffer.
+    /// Each block is compacted before its output is copied over it.
+    fn decode_whitespace_in_place(self, buf: &mut [u8]) -> Result<usize, CodecError> {
+        let (data_end, trailer) = self.split_trailer(buf);
+
+        let mut carry = Carry::default();
+        let mut block_output = [MaybeUninit::uninit(); decode_len_no_pad(WHITESPACE_BLOCK_LEN)];
+        let mut output_len = 0;
+        let mut data_len = 0;
+        let mut block_start = 0;
+        while block_start < data_end {
+            let block_end = data_end.min(block_start + WHITESPACE_BLOCK_LEN - 3);
+            let (len, block_data_len) = self
+                .decode_whitespace_groups(
+                    &buf[block_start..block_end],
+                    &mut carry,
+                    &mut block_output,
+                )
+                .map_err(|e| e.offset_by(block_start))?;
+            // Safety: the first len bytes were written
+            let block_output = unsafe { assume_init(&mut block_output[..len]) };
+            buf[output_len..output_len + len].copy_from_slice(block_output);
+            output_len += len;
+            data_len += block_data_len;
+            block_start = block_end;
+        }
+        self.check_padding(data_len, trailer)?;
+        // Safety: decoding only writes bytes to buf
+        let output = unsafe { as_uninit(&mut buf[output_len..]) };
+        output_len += (self.fns().decode)(output, carry.chars(), self.alphabet)?;
+        // The output is at least a char shorter than the data, so the last char is still there
+        self.check_trailing_bits(&buf[..data_end], data_len)?;
+        Ok(output_len)
+    }
+
+    /// Convert `input` to the encoding of `to` without decoding it, returning the output length.
+    ///
+    /// Only the chars are remapped, they're checked like [`DecodeOptions::decode`] does,
+    /// with the same errors. The output is the same as encoding the decoded data with `to`,
+    /// so padding and line wrapping can be added or removed on the way.
+    pub fn transcode(
+        self,
+        input: &[u8],
+        to: EncodeOptions,
+        output: &mut [u8],
+    ) -> Result<usize, CodecError> {
+        let (data_end, data_len, trailer) = self.split_padding(input);
+        let len = to.encode_len(decode_len_no_pad(data_len));
+        if output.len() < len {
+            return Err(CodecError::OutputLengthTooShort(output.len(), len));
+        }
+
+        let data = &input[..data_end];
+        let fns = self.fns();
+        let table = TranscodeTable::new(self.alphabet, to.alphabet);
+        if self.ignore_whitespace {
+            let mut buf = [0u8; WHITESPACE_BLOCK_LEN];
+            let mut output_len = 0;
+            for (i, block) in data.chunks(WHITESPACE_BLOCK_LEN).enumerate() {
+                let len = self
+                    .transcode_whitespace_block(block, to.alphabet, &table, &mut buf)
+                    .map_err(|e| e.offset_by(i * WHITESPACE_BLOCK_LEN))?;
+                // Only data with a length error can be longer than the output, which fails
+                // below once all its chars are checked
+                let len = len.min(output.len() - output_len);
+                output[output_len..output_len + len].copy_from_slice(&buf[..len]);
+                output_len += len;
+            }
+            self.check_padding(data_len, trailer)?;
+        } else {
+            self.check_padding(data_len, trailer)?;
+            // Transcoded in the output, a block at a time so it's still in cache
+            for (i, (block, output)) in data
+                .chunks(TRANSCODE_BLOCK_LEN)
+                .zip(output.chunks_mut(TRANSCODE_BLOCK_LEN))
+                .enumerate()
+            {
+                let output = &mut output[..block.len()];
+                output.copy_from_slice(block);
+                (fns.transcode)(output, self.alphabet, to.alphabet, &table)
+                    .map_err(|e| e.offset_by(i * TRANSCODE_BLOCK_LEN))?;
+            }
+        }
+        self.check_trailing_bits(data, data_len)?;
+        Ok(to.finish_transcode(output, data_len))
+    }
+
+    /// Convert the first `input_len` bytes of `buf` to the encoding of `to` in place,
+    /// like [`DecodeOptions::transcode`]. `buf` must be large enough for the output,
+    /// which is longer than the input if padding or line endings are added.
+    /// Returns the output length, on error the contents of `buf` are unspecified.
+    ///
+    /// # Panics
+    /// If `input_len` is larger than `buf`
+    pub fn transcode_in_place(
+        self,
+        buf: &mut [u8],
+        input_len: usize,
+        to: EncodeOptions,
+    ) -> Result<usize, CodecError> {
+        let (data_end, data_len, trailer) = self.split_padding(&buf[..input_len]);
+        let len = to.encode_len(decode_len_no_pad(data_len));
+        if buf.len() < len {
+            return Err(CodecError::OutputLengthTooShort(buf.len(), len));
+        }
+
+        // Checked before the last char is replaced, but reported after invalid chars
+        let trailing_bits = self.check_trailing_bits(&buf[..data_end], data_len);
+        let table = TranscodeTable::new(self.alphabet, to.alphabet);
+        if self.ignore_whitespace {
+            // Output is never longer than the chars read, so blocks aren't overwritten
+            // before they're compacted
+            let mut block_buf = [0u8; WHITESPACE_BLOCK_LEN];
+            let mut output_len = 0;
+            let mut block_start = 0;
+            while block_start < data_end {
+                let block_end = data_end.min(block_start + WHITESPACE_BLOCK_LEN);
+                let len = self
+                    .transcode_whitespace_block(
+                        &buf[block_start..block_end],
+                        to.alphabet,
+                        &table,
+                        &mut block_buf,
+                    )
+                    .map_err(|e| e.offset_by(block_start))?;
+                buf[output_len..output_len + len].copy_from_slice(&block_buf[..len]);
+                output_len += len;
+                block_start = block_end;
+            }
+            self.check_padding(data_len, trailer)?;
         } else {
-            avx2::decode_with_fallback(output, input)
+            self.check_padding(data_len, trailer)?;
+            (self.fns().transcode)(&mut buf[..data_end], self.alphabet, to.alphabet, &table)?;
+        }
+        trailing_bits?;
+        Ok(to.finish_transcode(buf, data_len))
+    }
+
+    /// Compact whitespace out of `block` into `buf` and transcode it there to `to`,
+    /// returning the compacted length. `table` is built from the two alphabets.
+    /// Errors have offsets into `block`.
+    fn transcode_whitespace_block(
+        &self,
+        block: &[u8],
+        to: &Alphabet,
+        table: &TranscodeTable,
+        buf: &mut [u8; WHITESPACE_BLOCK_LEN],
+    ) -> Result<usize, CodecError> {
+        let fns = self.fns();
+        let len = (fns.compact_whitespace)(buf, block);
+        (fns.transcode)(&mut buf[..len], self.alphabet, to, table)
+            .map_err(|e| uncompact_offset(e, block, 0))?;
+        Ok(len)
+    }
+
+    /// Decode into output, which must be at least `self.decoded_len(input)` long.
+    /// Returns the decoded length, all of which is written
+    fn decode_unchecked(
+        self,
+        input: &[u8],
+        output: &mut [MaybeUninit<u8>],
+    ) -> Result<usize, CodecError> {
+        if self.ignore_whitespace {
+            return self.decode_whitespace(input, output);
+        }
+
+        let (data_len, trailer) = self.split_trailer(input);
+        self.check_padding(data_len, trailer)?;
+        let output_len = (self.fns().decode)(output, &input[..data_len], self.alphabet)?;
+        self.check_trailing_bits(&input[..data_len], data_len)?;
+        Ok(output_len)
+    }
+
+    /// Decode input containing whitespace, by compacting it into blocks on the stack
+    fn decode_whitespace(
+        self,
+        input: &[u8],
+        output: &mut [MaybeUninit<u8>],
+    ) -> Result<usize, CodecError> {
+        let (data_end, trailer) = self.split_trailer(input);
+        let data = &input[..data_end];
+
+        let mut carry = Carry::default();
+        let (mut output_len, data_len) = self.decode_whitespace_groups(data, &mut carry, output)?;
+        self.check_padding(data_len, trailer)?;
+        output_len += (self.fns().decode)(&mut output[output_len..], carry.chars(), self.alphabet)?;
+        self.check_trailing_bits(data, data_len)?;
+        Ok(output_len)
+    }
+
+    /// Decode the full groups of `carry` followed by `data` with whitespace removed,
+    /// leaving the chars after the last full group in `carry`.
+    /// Chars already in `carry` must be part of the alphabet, the ones left in it are checked.
+    ///
+    /// Returns the decoded length, and the number of chars in `data` without whitespace.
+    fn decode_whitespace_groups(
+        self,
+        data: &[u8],
+        carry: &mut Carry,
+        output: &mut [MaybeUninit<u8>],
+    ) -> Result<(usize, usize), CodecError> {
+        let mut buf = [0u8; WHITESPACE_BLOCK_LEN];
+        buf[..carry.len].copy_from_slice(carry.chars());
+        // chars carried over from the previous block, always < 4
+        let mut buf_len = carry.len;
+        // chars of buf decoded so far, including the initial carry
+        let mut compact_len = 0;
+        let mut output_len = 0;
+        let fns = self.fns();
+        for block in data.chunks(WHITESPACE_BLOCK_LEN - 3) {
+            buf_len += (fns.compact_whitespace)(&mut buf[buf_len..], block);
+            let groups_len = buf_len / 4 * 4;
+            output_len +=
+                (fns.decode)(&mut output[output_len..], &buf[..groups_len], self.alphabet)
+                    .map_err(|e| uncompact_offset(e.offset_by(compact_len), data, carry.len))?;
+            buf.copy_within(groups_len..buf_len, 0);
+            buf_len -= groups_len;
+            compact_len += groups_len;
+        }
+
+        if let Some(i) = buf[..buf_len]
+            .iter()
+            .position(|&c| self.alphabet.value(c).is_none())
+        {
+            let err = CodecError::InvalidByte {
+                offset: compact_len + i,
+                byte: buf[i],
+            };
+            return Err(uncompact_offset(err, data, carry.len));
         }
+        let data_len = compact_len + buf_len - carry.len;
+        carry.chars[..buf_len].copy_from_slice(&buf[..buf_len]);
+        carry.len = buf_len;
+        Ok((output_len, data_len))
+    }
+
+    /// In canonical mode, check that the unused low bits of the last char are zero.
+    /// `data` ends with the last char, and holds `data_len` chars once whitespace is removed.
+    fn check_trailing_bits(&self, data: &[u8], data_len: usize) -> Result<(), CodecError> {
+        match data.last().and_then(|&c| self.alphabet.value(c)) {
+            Some(value) if self.canonical && value & unused_bits(data_len) != 0 => {
+                Err(CodecError::TrailingBits(data.len() - 1))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the length of the data and trailing padding against the padding policy.
+    /// Errors have the offsets of `trailer` in the input.
+    fn check_padding(&self, data_len: usize, trailer: Trailer) -> Result<(), CodecError> {
+        let expected_pad = (4 - data_len % 4) % 4;
+        // Only the padded encoding is canonical
+        let padding = match self.padding {
+            Padding::Optional if self.canonical => Padding::Required,
+            padding => padding,
+        };
+        match (padding, trailer.pad_len) {
+            _ if data_len % 4 == 1 => Err(CodecError::InputModError(trailer.end)),
+            (Padding::Required, n) | (Padding::Optional, n @ 1..) if n != expected_pad => {
+                Err(CodecError::InvalidPadding(trailer.pad_offset))
+            }
+            (Padding::Forbidden, 1..) => Err(CodecError::InvalidPadding(trailer.pad_offset)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Spread the first `encoded_len` chars of `buf` into lines, from the last line to the first
+/// so no line is overwritten before it's moved.
+fn wrap_in_place(buf: &mut [u8], encoded_len: usize, wrap: LineWrap) {
+    let ending = wrap.ending().as_bytes();
+    let wrapped_line_len = wrap.line_len() + ending.len();
+    let lines = encoded_len.div_ceil(wrap.line_len());
+    for line in (1..lines).rev() {
+        let start = line * wrap.line_len();
+        let end = encoded_len.min(start + wrap.line_len());
+        let dest = line * wrapped_line_len;
+        buf.copy_within(start..end, dest);
+        buf[dest - ending.len()..dest].copy_from_slice(ending);
+    }
+}
+
+/// View `output` as possibly uninitialized, to encode or decode into it.
+///
+/// # Safety
+/// Only initialized bytes may be written through the result. The backends only write
+/// encoded or decoded bytes to their output.
+pub(crate) unsafe fn as_uninit(output: &mut [u8]) -> &mut [MaybeUninit<u8>] {
+    &mut *(output as *mut [u8] as *mut [MaybeUninit<u8>])
+}
+
+/// View `output` as bytes, once all of it was written.
+///
+/// # Safety
+/// Every byte of `output` must be initialized.
+unsafe fn assume_init(output: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    &mut *(output as *mut [MaybeUninit<u8>] as *mut [u8])
+}
+
+/// Check that an output of `output_len` bytes can hold the `len` bytes written to it
+pub(crate) fn check_output_len(output_len: usize, len: usize) -> Result<(), CodecError> {
+    if output_len < len {
+        Err(CodecError::OutputLengthTooShort(output_len, len))
+    } else {
+        Ok(())
+    }
+}
+
+/// Copy `src` to the start of possibly uninitialized `dest`
+pub(crate) fn copy_to_uninit(dest: &mut [MaybeUninit<u8>], src: &[u8]) {
+    for (dest, &c) in dest[..src.len()].iter_mut().zip(src) {
+        dest.write(c);
+    }
+}
+
+/// Bits of the last of `data_len` chars that aren't part of the data
+fn unused_bits(data_len: usize) -> u8 {
+    match data_len % 4 {
+        2 => 0x0f,
+        3 => 0x03,
+        _ => 0,
     }
 }
 
+/// Length of `input` without its trailing padding
+fn data_end(input: &[u8]) -> usize {
+    input.iter().rposition(|&c| c != b'=').map_or(0, |i| i + 1)
+}
+
+/// Length of `input` without its trailing padding, which may have whitespace mixed in
+fn data_end_whitespace(input: &[u8]) -> usize {
+    input
+        .iter()
+        .rposition(|&c| c != b'=' && !is_whitespace(c))
+        .map_or(0, |i| i + 1)
+}
+
+/// Map the offset of an invalid byte in the compacted data back to its offset in `data`.
+/// The compacted data starts with `carry_len` chars from before `data`.
+fn uncompact_offset(err: CodecError, data: &[u8], carry_len: usize) -> CodecError {
+    match err {
+        CodecError::InvalidByte { offset, byte } => {
+            let offset = data
+                .iter()
+                .enumerate()
+                .filter(|(_, &c)| !is_whitespace(c))
+                .nth(offset - carry_len)
+                .map_or(offset, |(i, _)| i);
+            CodecError::InvalidByte { offset, byte }
+        }
+        other => other,
+    }
+}
+
+#[cfg(feature = "alloc")]
 pub fn encode(input: &[u8]) -> String {
     EncodeOptions::default().encode(input)
 }
 
+/// Append the encoding of `input` to `output`, see [`EncodeOptions::encode_to_string`]
+#[cfg(feature = "alloc")]
+pub fn encode_to_string(input: &[u8], output: &mut String) -> usize {
+    EncodeOptions::default().encode_to_string(input, output)
+}
+
 pub fn encode_mut(input: &[u8], output: &mut [u8]) -> Result<usize, CodecError> {
     EncodeOptions::default().encode_mut(input, output)
 }
 
+/// Encode into uninitialized `output`, see [`EncodeOptions::encode_uninit`]
+pub fn encode_uninit<'o>(
+    input: &[u8],
+    output: &'o mut [MaybeUninit<u8>],
+) -> Result<&'o mut [u8], CodecError> {
+    EncodeOptions::default().encode_uninit(input, output)
+}
+
+/// Encode the first `data_len` bytes of `buf` in place with padding,
+/// see [`EncodeOptions::encode_in_place`]
+pub fn encode_in_place(buf: &mut [u8], data_len: usize) -> Result<usize, CodecError> {
+    EncodeOptions::default().encode_in_place(buf, data_len)
+}
+
+/// Exact decoded length of padded base64 `input`, see [`DecodeOptions::decoded_len`]
+pub fn decoded_len(input: &[u8]) -> Result<usize, CodecError> {
+    DecodeOptions::default().decoded_len(input)
+}
+
+#[cfg(feature = "alloc")]
 pub fn decode(input: &[u8]) -> Result<Vec<u8>, CodecError> {
     DecodeOptions::default().decode(input)
 }
 
+/// Check that `input` is valid padded base64, see [`DecodeOptions::validate`]
+pub fn validate(input: &[u8]) -> Result<(), CodecError> {
+    DecodeOptions::default().validate(input)
+}
+
+/// Convert padded base64 `input` from one alphabet to another without decoding it,
+/// see [`DecodeOptions::transcode`]
+pub fn transcode(
+    input: &[u8],
+    from: &Alphabet,
+    to: &Alphabet,
+    output: &mut [u8],
+) -> Result<usize, CodecError> {
+    let to = EncodeOptions::default().alphabet(to);
+    DecodeOptions::default()
+        .alphabet(from)
+        .transcode(input, to, output)
+}
+
+/// Convert the first `input_len` bytes of `buf`, padded base64, from one alphabet
+/// to another in place, see [`DecodeOptions::transcode_in_place`]
+pub fn transcode_in_place(
+    buf: &mut [u8],
+    input_len: usize,
+    from: &Alphabet,
+    to: &Alphabet,
+) -> Result<usize, CodecError> {
+    let to = EncodeOptions::default().alphabet(to);
+    DecodeOptions::default()
+        .alphabet(from)
+        .transcode_in_place(buf, input_len, to)
+}
+
+/// Decode padded base64 in place, see [`DecodeOptions::decode_in_place`]
+pub fn decode_in_place(buf: &mut [u8]) -> Result<&mut [u8], CodecError> {
+    DecodeOptions::default().decode_in_place(buf)
+}
+
+/// Append the decoding of padded base64 `input` to `output`, see [`DecodeOptions::decode_to_vec`]
+#[cfg(feature = "alloc")]
+pub fn decode_to_vec(input: &[u8], output: &mut Vec<u8>) -> Result<usize, CodecError> {
+    DecodeOptions::default().decode_to_vec(input, output)
+}
+
 pub fn decode_mut(input: &[u8], output: &mut [u8]) -> Result<usize, CodecError> {
     DecodeOptions::default().decode_mut(input, output)
 }
+
+/// Decode padded base64 into uninitialized `output`, see [`DecodeOptions::decode_uninit`]
+pub fn decode_uninit<'o>(
+    input: &[u8],
+    output: &'o mut [MaybeUninit<u8>],
+) -> Result<&'o mut [u8], CodecError> {
+    DecodeOptions::default().decode_uninit(input, output)
+}
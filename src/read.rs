@@ -0,0 +1,119 @@
+use std::io::{self, Read};
+
+use crate::{decode_len, CodecError, DecodeOptions, Decoder};
+
+/// Size of the buffer base64 is read into
+const BUF_LEN: usize = 32 * 1024;
+
+/// Decodes base64 read from the inner reader.
+///
+/// This is a [`Decoder`] reading from the inner reader, so partial 4 char groups are kept
+/// between reads, and trailing padding and whitespace are counted instead of buffered.
+/// Decode errors are returned as [`io::ErrorKind::InvalidData`],
+/// wrapping a [`CodecError`] with offsets into the whole input.
+/// After a decode error, every later read returns the same error.
+///
+/// ```
+/// use std::io::Read;
+/// use bs64::{DecodeOptions, DecoderReader};
+///
+/// let mut reader = DecoderReader::new(&b"aGVsbG8="[..], DecodeOptions::default());
+/// let mut output = Vec::new();
+/// reader.read_to_end(&mut output).unwrap();
+/// assert_eq!(output, b"hello");
+/// ```
+pub struct DecoderReader<'a, R: Read> {
+    decoder: Decoder<'a>,
+    inner: R,
+    /// Base64 read from the inner reader
+    input: Vec<u8>,
+    /// Decoded bytes, of which output[output_pos..output_len] are not read yet
+    output: Vec<u8>,
+    output_pos: usize,
+    output_len: usize,
+    /// Set once the inner reader reached EOF and all of its input was decoded
+    finished: bool,
+    /// The first decode error, returned by every read after it
+    error: Option<CodecError>,
+}
+
+impl<'a, R: Read> DecoderReader<'a, R> {
+    pub fn new(inner: R, options: DecodeOptions<'a>) -> Self {
+        DecoderReader {
+            decoder: Decoder::new(options),
+            inner,
+            input: vec![0; BUF_LEN],
+            // Up to 3 chars are left from the last read
+            output: vec![0; decode_len(BUF_LEN + 3)],
+            output_pos: 0,
+            output_len: 0,
+            finished: false,
+            error: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Reading from the inner reader directly will corrupt the output
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read more input, and decode it into output,
+    /// which must be at least as long as the output buffer
+    fn decode_more(&mut self, output: &mut [u8]) -> io::Result<usize> {
+        let read_len = loop {
+            match self.inner.read(&mut self.input) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        };
+        self.finished = read_len == 0;
+        let result = if self.finished {
+            self.decoder.finalize(output)
+        } else {
+            self.decoder.update(&self.input[..read_len], output)
+        };
+        result.map_err(|e| {
+            self.error = Some(e.duplicate());
+            e.into()
+        })
+    }
+}
+
+impl<R: Read> Read for DecoderReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = &self.error {
+            return Err(e.duplicate().into());
+        }
+        while self.output_pos == self.output_len {
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+            if buf.len() >= self.output.len() {
+                // Skip the copy through the output buffer for large reads
+                match self.decode_more(buf)? {
+                    0 => continue,
+                    len => return Ok(len),
+                }
+            }
+            let mut output = std::mem::take(&mut self.output);
+            let result = self.decode_more(&mut output);
+            self.output = output;
+            self.output_len = result?;
+            self.output_pos = 0;
+        }
+
+        let len = buf.len().min(self.output_len - self.output_pos);
+        buf[..len].copy_from_slice(&self.output[self.output_pos..self.output_pos + len]);
+        self.output_pos += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,128 @@
+//! Serialize byte fields as base64 strings, with `#[serde(with = "...")]`.
+//!
+//! Each module serializes anything that is `AsRef<[u8]>`, and deserializes anything that
+//! can be built from a `Vec<u8>`, such as `Vec<u8>`, `[u8; N]` or `bytes::Bytes`.
+//! Deserializing decodes straight from the input string when the format can borrow it.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Message {
+//!     #[serde(with = "bs64::serde::standard")]
+//!     body: Vec<u8>,
+//!     #[serde(with = "bs64::serde::url_safe_no_pad")]
+//!     key: [u8; 4],
+//! }
+//!
+//! let message = Message { body: b"hello".to_vec(), key: [0xfb, 0xff, 0, 1] };
+//! let json = serde_json::to_string(&message).unwrap();
+//! assert_eq!(json, r#"{"body":"aGVsbG8=","key":"-_8AAQ"}"#);
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use ::serde::de::{self, Deserializer, Visitor};
+use ::serde::Serializer;
+
+use crate::{DecodeOptions, EncodeOptions};
+
+fn serialize<S: Serializer>(
+    bytes: &[u8],
+    serializer: S,
+    options: EncodeOptions,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&options.encode(bytes))
+}
+
+fn deserialize<'de, D, T>(deserializer: D, options: DecodeOptions) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<Vec<u8>>,
+{
+    deserializer.deserialize_str(Base64Visitor {
+        options,
+        output: PhantomData,
+    })
+}
+
+struct Base64Visitor<'a, T> {
+    options: DecodeOptions<'a>,
+    output: PhantomData<T>,
+}
+
+impl<T: TryFrom<Vec<u8>>> Visitor<'_> for Base64Visitor<'_, T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a base64 string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<T, E> {
+        let bytes = self.options.decode(v).map_err(E::custom)?;
+        let len = bytes.len();
+        T::try_from(bytes).map_err(|_| E::invalid_length(len, &self))
+    }
+}
+
+macro_rules! base64_module {
+    ($(#[$doc:meta])* $name:ident, $encode:expr, $decode:expr) => {
+        $(#[$doc])*
+        pub mod $name {
+            use super::*;
+
+            pub fn serialize<S: Serializer, T: AsRef<[u8]> + ?Sized>(
+                bytes: &T,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                super::serialize(bytes.as_ref(), serializer, $encode)
+            }
+
+            pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+            where
+                D: Deserializer<'de>,
+                T: TryFrom<Vec<u8>>,
+            {
+                super::deserialize(deserializer, $decode)
+            }
+        }
+    };
+}
+
+base64_module!(
+    /// The standard alphabet, with padding
+    standard,
+    EncodeOptions::default(),
+    DecodeOptions::default()
+);
+
+base64_module!(
+    /// The standard alphabet, without padding
+    standard_no_pad,
+    EncodeOptions::default().padding(false),
+    DecodeOptions::default().padding(crate::Padding::Forbidden)
+);
+
+base64_module!(
+    /// The URL-safe alphabet, with padding
+    url_safe,
+    EncodeOptions::default().alphabet(&crate::alphabet::URL_SAFE),
+    DecodeOptions::default().alphabet(&crate::alphabet::URL_SAFE)
+);
+
+base64_module!(
+    /// The URL-safe alphabet, without padding
+    url_safe_no_pad,
+    EncodeOptions::default()
+        .alphabet(&crate::alphabet::URL_SAFE)
+        .padding(false),
+    DecodeOptions::default()
+        .alphabet(&crate::alphabet::URL_SAFE)
+        .padding(crate::Padding::Forbidden)
+);
@@ -1,21 +1,26 @@
-use self::luts::{BADCHAR, D0, D1, D2, D3, E0, E1, E2};
+use self::luts::BADCHAR;
 
 // Inner loop code ported from here:
 // https://github.com/lemire/fastbase64/blob/master/src/chromiumbase64.c
 // Iterators used to guide the compiler to vectorize the loop + produce simd instructions
 
+use core::mem::MaybeUninit;
+
 use super::CodecError;
+use crate::alphabet::{Alphabet, STANDARD};
+use crate::{as_uninit, check_output_len, copy_to_uninit, encode_len, encode_len_no_pad};
+use crate::{decode_len_no_pad, LineWrap};
 
-mod luts;
+pub(crate) mod luts;
 
-#[repr(packed(1))]
+#[repr(C, packed(1))]
 struct Bytes {
     t1: u8,
     t2: u8,
     t3: u8,
 }
 
-#[repr(packed(1))]
+#[repr(C, packed(1))]
 struct Chars {
     d1: u8,
     d2: u8,
@@ -23,61 +28,114 @@ struct Chars {
     d4: u8,
 }
 
-fn encode_any_inner(src: &[Bytes], dest: &mut [Chars]) -> usize {
+/// Output of a group, written a char at a time like the fields of [`Chars`]
+type CharsUninit = [MaybeUninit<u8>; 4];
+
+fn encode_any_inner(src: &[Bytes], dest: &mut [CharsUninit], alphabet: &Alphabet) -> usize {
+    let (e0, e1) = (&alphabet.e0, &alphabet.e1);
     // We use a iterator loop here to avoid bounds checks
     // This allows the compiler to vectorize the loop
     // and generate SIMD instructions
     // See this blog post for more details:
     // https://www.nickwilcox.com/blog/autovec/
     for (dest, src) in dest.iter_mut().zip(src.iter()) {
-        let (t1, t2, t3) = (src.t1, src.t2, src.t3);
-        dest.d1 = E0[t1 as usize];
-        dest.d2 = E1[(((t1 & 0x03) << 4) | ((t2 >> 4) & 0x0F)) as usize];
-        dest.d3 = E1[(((t2 & 0x0F) << 2) | ((t3 >> 6) & 0x03)) as usize];
-        dest.d4 = E2[t3 as usize];
+        encode_group(src, dest, e0, e1);
     }
 
     dest.len() * 4
 }
 
-fn encode_32_inner(src: &[Bytes], dest: &mut [Chars]) -> usize {
+fn encode_32_inner(src: &[Bytes], dest: &mut [CharsUninit], alphabet: &Alphabet) -> usize {
+    let (e0, e1) = (&alphabet.e0, &alphabet.e1);
     for (dest, src) in dest.iter_mut().zip(src.iter()) {
-        let (t1, t2, t3) = (src.t1, src.t2, src.t3);
-        dest.d1 = E0[t1 as usize];
-        dest.d2 = E1[(((t1 & 0x03) << 4) | ((t2 >> 4) & 0x0F)) as usize];
-        dest.d3 = E1[(((t2 & 0x0F) << 2) | ((t3 >> 6) & 0x03)) as usize];
-        dest.d4 = E2[t3 as usize];
+        encode_group(src, dest, e0, e1);
     }
 
     32
 }
 
+#[inline(always)]
+fn encode_group(src: &Bytes, dest: &mut CharsUninit, e0: &[u8; 256], e1: &[u8; 256]) {
+    let (t1, t2, t3) = (src.t1, src.t2, src.t3);
+    let [d1, d2, d3, d4] = dest;
+    d1.write(e0[t1 as usize]);
+    d2.write(e1[(((t1 & 0x03) << 4) | ((t2 >> 4) & 0x0F)) as usize]);
+    d3.write(e1[(((t2 & 0x0F) << 2) | ((t3 >> 6) & 0x03)) as usize]);
+    d4.write(e1[t3 as usize]);
+}
+
 /// Encode 24 bytes from src slice to 32 destination slice
 ///
 /// # Safety
 /// - No checks are performed on the slice lengths
-unsafe fn encode_32(src: &[u8], dest: &mut [u8]) -> usize {
-    let src = std::slice::from_raw_parts(src.as_ptr() as *const Bytes, 8);
-    let dest = std::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut Chars, 8);
+unsafe fn encode_32(src: &[u8], dest: &mut [MaybeUninit<u8>], alphabet: &Alphabet) -> usize {
+    let src = core::slice::from_raw_parts(src.as_ptr() as *const Bytes, 8);
+    let dest = core::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut CharsUninit, 8);
 
-    encode_32_inner(src, dest)
+    encode_32_inner(src, dest, alphabet)
 }
 
 /// Encode any length src and destination slice
 ///
 /// # Safety
 /// - No checks are performed on the destination slice length
-unsafe fn encode_any(src: &[u8], dest: &mut [u8]) -> usize {
+unsafe fn encode_any(src: &[u8], dest: &mut [MaybeUninit<u8>], alphabet: &Alphabet) -> usize {
     let src_len = src.len() / 3;
-    let src = std::slice::from_raw_parts(src.as_ptr() as *const Bytes, src_len);
-    let dest = std::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut Chars, src_len);
+    let src = core::slice::from_raw_parts(src.as_ptr() as *const Bytes, src_len);
+    let dest = core::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut CharsUninit, src_len);
 
-    encode_any_inner(src, dest)
+    encode_any_inner(src, dest, alphabet)
 }
 
-/// Encode src slice to dest slice
+/// Encode src slice to dest slice using the standard alphabet, with padding
 /// Returns the number of bytes written to dest
+///
+/// # Panics
+/// If dest is shorter than `encode_len(src.len())`
 pub fn encode(src: &[u8], dest: &mut [u8]) -> usize {
+    let len = encode_len(src.len());
+    assert!(
+        dest.len() >= len,
+        "output length {} is < expected length {len}",
+        dest.len()
+    );
+    // Safety: encoding only writes chars to dest
+    let len = encode_with_alphabet_uninit(src, unsafe { as_uninit(dest) }, &STANDARD);
+    pad(dest, len)
+}
+
+/// Pad the `len` chars encoded at the start of dest to a multiple of 4
+/// Returns the padded length
+pub(crate) fn pad(dest: &mut [u8], len: usize) -> usize {
+    let padded_len = len.next_multiple_of(4);
+    dest[len..padded_len].fill(b'=');
+    padded_len
+}
+
+/// Encode src slice to dest slice using the given alphabet, without padding
+/// Returns the number of bytes written to dest,
+/// or an error if dest is shorter than `encode_len_no_pad(src.len())`
+pub fn encode_with_alphabet(
+    src: &[u8],
+    dest: &mut [u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    check_output_len(dest.len(), encode_len_no_pad(src.len()))?;
+    // Safety: encoding only writes chars to dest
+    Ok(encode_with_alphabet_uninit(
+        src,
+        unsafe { as_uninit(dest) },
+        alphabet,
+    ))
+}
+
+/// [`encode_with_alphabet`] into a possibly uninitialized dest, which must be long enough
+pub(crate) fn encode_with_alphabet_uninit(
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+    alphabet: &Alphabet,
+) -> usize {
+    let (e0, e1) = (&alphabet.e0, &alphabet.e1);
     let src_iter = src.chunks(24);
     let dest_iter = dest.chunks_mut(32);
     let data_iter = src_iter.zip(dest_iter);
@@ -89,7 +147,7 @@ pub fn encode(src: &[u8], dest: &mut [u8]) -> usize {
         // Final chunk
         if src.len() < 24 {
             if src.len() > 2 {
-                unsafe { dest_i += encode_any(src, dest) }
+                unsafe { dest_i += encode_any(src, dest, alphabet) }
                 src_i = (dest_i / 4) * 3;
             }
 
@@ -97,26 +155,23 @@ pub fn encode(src: &[u8], dest: &mut [u8]) -> usize {
                 0 => (),
                 1 => {
                     let t1 = src[src_i];
-                    dest[dest_i] = E0[t1 as usize];
-                    dest[dest_i + 1] = E1[((t1 & 0x03) << 4) as usize];
-                    dest[dest_i + 2] = b'=';
-                    dest[dest_i + 3] = b'=';
-                    dest_i += 4;
+                    dest[dest_i].write(e0[t1 as usize]);
+                    dest[dest_i + 1].write(e1[((t1 & 0x03) << 4) as usize]);
+                    dest_i += 2;
                 }
                 _ => {
                     let (t1, t2) = (src[src_i], src[src_i + 1]);
-                    dest[dest_i] = E0[t1 as usize];
-                    dest[dest_i + 1] = E1[(((t1 & 0x03) << 4) | ((t2 >> 4) & 0x0F)) as usize];
-                    dest[dest_i + 2] = E1[((t2 & 0x0F) << 2) as usize];
-                    dest[dest_i + 3] = b'=';
-                    dest_i += 4;
+                    dest[dest_i].write(e0[t1 as usize]);
+                    dest[dest_i + 1].write(e1[(((t1 & 0x03) << 4) | ((t2 >> 4) & 0x0F)) as usize]);
+                    dest[dest_i + 2].write(e1[((t2 & 0x0F) << 2) as usize]);
+                    dest_i += 3;
                 }
             }
             break;
         }
 
         unsafe {
-            encode_32(src, dest);
+            encode_32(src, dest, alphabet);
         }
         num_chunks += 1;
     }
@@ -124,54 +179,225 @@ pub fn encode(src: &[u8], dest: &mut [u8]) -> usize {
     dest_i + (num_chunks * 32)
 }
 
-fn decode_any_inner(src: &[Chars], dest: &mut [Bytes]) -> Result<usize, CodecError> {
-    for (dst, src) in dest.iter_mut().zip(src.iter()) {
+/// Encode src slice to dest slice using the given alphabet, without padding,
+/// breaking lines as configured by wrap
+/// Returns the number of bytes written to dest,
+/// or an error if dest is shorter than `wrap.wrapped_len(encode_len_no_pad(src.len()))`
+pub fn encode_wrapped(
+    src: &[u8],
+    dest: &mut [u8],
+    alphabet: &Alphabet,
+    wrap: LineWrap,
+) -> Result<usize, CodecError> {
+    check_output_len(dest.len(), wrap.wrapped_len(encode_len_no_pad(src.len())))?;
+    // Safety: encoding only writes chars and line endings to dest
+    let dest = unsafe { as_uninit(dest) };
+    Ok(encode_wrapped_from(src, dest, alphabet, wrap, 0))
+}
+
+/// Wrapped encode continuing a line that already holds line_pos chars,
+/// into a dest that must be long enough
+pub(crate) fn encode_wrapped_from(
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+    alphabet: &Alphabet,
+    wrap: LineWrap,
+    line_pos: usize,
+) -> usize {
+    let ending = wrap.ending().as_bytes();
+    let mut line_left = wrap.line_len() - line_pos;
+    let mut src = src;
+    let mut dest_i = 0;
+
+    while !src.is_empty() {
+        if line_left == 0 {
+            copy_to_uninit(&mut dest[dest_i..], ending);
+            dest_i += ending.len();
+            line_left = wrap.line_len();
+        }
+
+        let (line, rest) = src.split_at((line_left / 4 * 3).min(src.len()));
+        let len = encode_with_alphabet_uninit(line, &mut dest[dest_i..], alphabet);
+        dest_i += len;
+        line_left = line_left.saturating_sub(len);
+        src = rest;
+    }
+
+    dest_i
+}
+
+/// Copy src slice to dest slice, skipping spaces, tabs, CR and LF
+/// dest must be at least as long as src
+/// Returns the number of bytes written to dest
+pub fn compact_whitespace(src: &[u8], dest: &mut [u8]) -> usize {
+    let mut dest_i = 0;
+    for &c in src {
+        dest[dest_i] = c;
+        dest_i += !crate::is_whitespace(c) as usize;
+    }
+    dest_i
+}
+
+/// Check that every char of src is part of the alphabet, without decoding it.
+/// The input must not contain padding, its length isn't checked.
+pub fn validate_with_alphabet(src: &[u8], alphabet: &Alphabet) -> Result<(), CodecError> {
+    let d0 = &alphabet.d[0];
+    for (i, chunk) in src.chunks(32).enumerate() {
+        // OR-ing the lookups of a whole chunk keeps the loop free of branches
+        if chunk.iter().fold(0, |x, &c| x | d0[c as usize]) >= BADCHAR {
+            return Err(invalid_byte(chunk, i * 32, alphabet));
+        }
+    }
+    Ok(())
+}
+
+/// Char to char table converting one alphabet to another.
+/// Built once per transcoding, and shared by the blocks and tails it's split into.
+pub(crate) struct TranscodeTable([u8; 256]);
+
+impl TranscodeTable {
+    pub(crate) fn new(from: &Alphabet, to: &Alphabet) -> Self {
+        // Alphabets are ASCII, so chars that aren't part of `from` are marked with the high bit
+        let mut table = [0x80u8; 256];
+        for (&f, &t) in from.chars.iter().zip(&to.chars) {
+            table[f as usize] = t;
+        }
+        TranscodeTable(table)
+    }
+}
+
+/// Convert the chars of buf from one alphabet to another in place, checking that they're
+/// part of `from`. The input must not contain padding, its length isn't checked.
+pub fn transcode_with_alphabets(
+    buf: &mut [u8],
+    from: &Alphabet,
+    to: &Alphabet,
+) -> Result<(), CodecError> {
+    transcode_with_table(buf, from, &TranscodeTable::new(from, to))
+}
+
+/// [`transcode_with_alphabets`] with a table built by the caller from `from`
+pub(crate) fn transcode_with_table(
+    buf: &mut [u8],
+    from: &Alphabet,
+    table: &TranscodeTable,
+) -> Result<(), CodecError> {
+    let table = &table.0;
+    let rest_offset = buf.len() / 32 * 32;
+    let mut chunks = buf.chunks_exact_mut(32);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        // The chunk is translated in place, a copy keeps the invalid char for the error.
+        // As words, it's copied in two moves instead of one per byte.
+        let original = [0, 16].map(|i| u128::from_ne_bytes(chunk[i..i + 16].try_into().unwrap()));
+        let mut x = 0;
+        for c in chunk {
+            *c = table[*c as usize];
+            x |= *c;
+        }
+        if x & 0x80 != 0 {
+            return Err(invalid_byte_in_words(original, i * 32, from));
+        }
+    }
+
+    let rest = chunks.into_remainder();
+    if rest.iter().any(|&c| table[c as usize] & 0x80 != 0) {
+        return Err(invalid_byte(rest, rest_offset, from));
+    }
+    for c in rest {
+        *c = table[*c as usize];
+    }
+    Ok(())
+}
+
+/// [`invalid_byte`] for a chunk copied as words
+#[cold]
+fn invalid_byte_in_words(words: [u128; 2], offset: usize, alphabet: &Alphabet) -> CodecError {
+    invalid_byte(
+        words.map(u128::to_ne_bytes).as_flattened(),
+        offset,
+        alphabet,
+    )
+}
+
+/// Error for the first char of src that isn't part of the alphabet,
+/// where src starts at offset in the input
+fn invalid_byte(src: &[u8], offset: usize, alphabet: &Alphabet) -> CodecError {
+    match src.iter().position(|&c| alphabet.value(c).is_none()) {
+        Some(i) => CodecError::InvalidByte {
+            offset: offset + i,
+            byte: src[i],
+        },
+        None => CodecError::Unknown,
+    }
+}
+
+fn decode_any_inner(
+    src: &[Chars],
+    dest: &mut [MaybeUninit<Bytes>],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    let [d0, d1, d2, d3] = &alphabet.d;
+    for (i, (dst, src)) in dest.iter_mut().zip(src.iter()).enumerate() {
         let x =
-            D0[src.d1 as usize] | D1[src.d2 as usize] | D2[src.d3 as usize] | D3[src.d4 as usize];
+            d0[src.d1 as usize] | d1[src.d2 as usize] | d2[src.d3 as usize] | d3[src.d4 as usize];
 
         let x0: *const u8 = &x as *const u32 as *const u8;
         let x1 = unsafe { x0.offset(1) };
         let x2 = unsafe { x0.offset(2) };
 
         if x >= BADCHAR {
-            let str = format!("{} {} {} {}", src.d1, src.d2, src.d3, src.d4);
-            return Err(CodecError::InvalidInput(str));
+            let group = [src.d1, src.d2, src.d3, src.d4];
+            return Err(invalid_byte(&group, i * 4, alphabet));
         }
         unsafe {
-            dst.t1 = *x0;
-            dst.t2 = *x1;
-            dst.t3 = *x2;
+            dst.write(Bytes {
+                t1: *x0,
+                t2: *x1,
+                t3: *x2,
+            });
         }
     }
 
     Ok(dest.len() * 3)
 }
 
-unsafe fn decode_any(src: &[u8], dest: &mut [u8]) -> Result<usize, CodecError> {
+unsafe fn decode_any(
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
     let src_len = src.len() / 4;
-    let src = std::slice::from_raw_parts(src.as_ptr() as *const Chars, src_len);
-    let dest = std::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut Bytes, src_len);
+    let src = core::slice::from_raw_parts(src.as_ptr() as *const Chars, src_len);
+    let dest =
+        core::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut MaybeUninit<Bytes>, src_len);
 
-    decode_any_inner(src, dest)
+    decode_any_inner(src, dest, alphabet)
 }
 
-fn decode_32_inner(src: &[Chars], dest: &mut [Bytes]) -> Result<usize, CodecError> {
-    for (dst, src) in dest.iter_mut().zip(src.iter()) {
+fn decode_32_inner(
+    src: &[Chars],
+    dest: &mut [MaybeUninit<Bytes>],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    let [d0, d1, d2, d3] = &alphabet.d;
+    for (i, (dst, src)) in dest.iter_mut().zip(src.iter()).enumerate() {
         let x: u32 =
-            D0[src.d1 as usize] | D1[src.d2 as usize] | D2[src.d3 as usize] | D3[src.d4 as usize];
+            d0[src.d1 as usize] | d1[src.d2 as usize] | d2[src.d3 as usize] | d3[src.d4 as usize];
 
         let x0: *const u8 = &x as *const u32 as *const u8;
         let x1 = unsafe { x0.offset(1) };
         let x2 = unsafe { x0.offset(2) };
 
         if x >= BADCHAR {
-            let str = format!("{} {} {} {}", src.d1, src.d2, src.d3, src.d4);
-            return Err(CodecError::InvalidInput(str));
+            let group = [src.d1, src.d2, src.d3, src.d4];
+            return Err(invalid_byte(&group, i * 4, alphabet));
         }
         unsafe {
-            dst.t1 = *x0;
-            dst.t2 = *x1;
-            dst.t3 = *x2;
+            dst.write(Bytes {
+                t1: *x0,
+                t2: *x1,
+                t3: *x2,
+            });
         }
     }
 
@@ -182,79 +408,117 @@ fn decode_32_inner(src: &[Chars], dest: &mut [Bytes]) -> Result<usize, CodecErro
 ///
 /// # Safety
 /// - No checks are performed on the slice lengths
-unsafe fn decode_32(src: &[u8], dest: &mut [u8]) -> Result<usize, CodecError> {
-    let src = std::slice::from_raw_parts(src.as_ptr() as *const Chars, 8);
-    let dest = std::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut Bytes, 8);
-
-    decode_32_inner(src, dest)
+unsafe fn decode_32(
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    let src = core::slice::from_raw_parts(src.as_ptr() as *const Chars, 8);
+    let dest = core::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut MaybeUninit<Bytes>, 8);
+
+    decode_32_inner(src, dest, alphabet)
 }
 
-/// Decode src slice to dest slice
+/// Decode src slice to dest slice using the standard alphabet
+/// Up to two padding chars are accepted, and the input length must be a multiple of 4
 /// Returns the number of bytes written to dest, or an error if input is invalid
 pub fn decode(src: &[u8], dest: &mut [u8]) -> Result<usize, CodecError> {
-    if src.len() == 0 {
-        return Ok(0);
-    }
+    decode_with_alphabet(unpad(src)?, dest, &STANDARD)
+}
 
+/// Strip the padding of src, which must be a multiple of 4 chars long
+pub(crate) fn unpad(src: &[u8]) -> Result<&[u8], CodecError> {
     /*
      * if padding is used, then the message must be at least
      * 4 chars and be a multiple of 4
      */
-    if src.len() < 4 || (src.len() % 4 != 0) {
+    if !src.len().is_multiple_of(4) {
         return Err(CodecError::InputModError(src.len())); /* error */
     }
 
     /* there can be at most 2 pad chars at the end */
-    let src = match src {
+    Ok(match src {
         [rest @ .., b'=', b'='] => rest,
         [rest @ .., b'='] => rest,
         _ => src,
-    };
+    })
+}
+
+/// Decode src slice to dest slice using the given alphabet
+/// The input must not contain padding
+/// Returns the number of bytes written to dest, or an error if input is invalid
+/// or dest is shorter than `decode_len_no_pad(src.len())`
+pub fn decode_with_alphabet(
+    src: &[u8],
+    dest: &mut [u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    check_output_len(dest.len(), decode_len_no_pad(src.len()))?;
+    // Safety: decoding only writes bytes to dest
+    decode_with_alphabet_uninit(src, unsafe { as_uninit(dest) }, alphabet)
+}
 
+/// [`decode_with_alphabet`] into a possibly uninitialized dest, which must be long enough
+pub(crate) fn decode_with_alphabet_uninit(
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    let [d0, d1, d2, _] = &alphabet.d;
     let len = src.len();
+    let leftover = len % 4;
+    if leftover == 1 {
+        return Err(CodecError::InputModError(len));
+    }
+
     let mut src_i = 0;
     let mut dest_i = 0;
-    loop {
-        // Use -4 to allow for padding bytes
-        if len.saturating_sub(4).saturating_sub(src_i) < 32 {
-            break;
-        }
+    while len - src_i >= 32 {
         unsafe {
-            decode_32(&src[src_i..src_i + 32], &mut dest[dest_i..dest_i + 24])?;
+            decode_32(
+                &src[src_i..src_i + 32],
+                &mut dest[dest_i..dest_i + 24],
+                alphabet,
+            )
+            .map_err(|e| e.offset_by(src_i))?;
         }
 
         src_i += 32;
         dest_i += 24;
     }
 
-    let leftover = len % 4;
-    let len_nopad = match leftover {
-        0 => len,
-        _ => (len / 4) * 4,
-    };
-
-    if len_nopad.saturating_sub(src_i) > 0 {
-        unsafe { dest_i += decode_any(&src[src_i..len_nopad], &mut dest[dest_i..])? }
+    let len_nopad = len - leftover;
+    if len_nopad > src_i {
+        unsafe {
+            dest_i += decode_any(&src[src_i..len_nopad], &mut dest[dest_i..], alphabet)
+                .map_err(|e| e.offset_by(src_i))?
+        }
         src_i = len_nopad;
     }
 
     match leftover {
         0 => (),
         2 => {
-            let x = D0[src[src_i] as usize] | D1[src[src_i + 1] as usize];
-            dest[dest_i] = x as u8; // i.e. second char
+            let x = d0[src[src_i] as usize] | d1[src[src_i + 1] as usize];
+            if x >= BADCHAR {
+                return Err(invalid_byte(&src[src_i..], src_i, alphabet));
+            }
+            dest[dest_i].write(x as u8); // i.e. second char
             dest_i += 1;
         }
         3 => {
             let y = &src[src_i..src_i + 3];
-            let x: u32 = D0[y[0] as usize] | D1[y[1] as usize] | D2[y[2] as usize]; /* 0x3c */
+            let x: u32 = d0[y[0] as usize] | d1[y[1] as usize] | d2[y[2] as usize]; /* 0x3c */
+            if x >= BADCHAR {
+                return Err(invalid_byte(y, src_i, alphabet));
+            }
 
             let x0: *const u8 = &x as *const u32 as *const u8;
             let x1 = unsafe { x0.offset(1) };
 
             unsafe {
-                dest[dest_i] = *x0;
-                dest[dest_i + 1] = *x1;
+                dest[dest_i].write(*x0);
+                dest[dest_i + 1].write(*x1);
             }
             dest_i += 2;
         }
@@ -264,25 +528,55 @@ pub fn decode(src: &[u8], dest: &mut [u8]) -> Result<usize, CodecError> {
     Ok(dest_i)
 }
 
+/// Decode src slice to dest slice using the standard alphabet, iterating over 32 char chunks
+/// Up to two padding chars are accepted
+/// Returns the number of bytes written to dest, or an error if input is invalid
 pub fn decode_iter(src: &[u8], dest: &mut [u8]) -> Result<usize, CodecError> {
-    if src.len() == 0 {
+    let src = match src {
+        [rest @ .., b'=', b'='] => rest,
+        [rest @ .., b'='] => rest,
+        _ => src,
+    };
+    decode_iter_with_alphabet(src, dest, &STANDARD)
+}
+
+/// Decode src slice to dest slice using the given alphabet, iterating over 32 char chunks
+/// The input must not contain padding
+/// Returns the number of bytes written to dest, or an error if input is invalid
+/// or dest is shorter than `decode_len_no_pad(src.len())`
+pub fn decode_iter_with_alphabet(
+    src: &[u8],
+    dest: &mut [u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    check_output_len(dest.len(), decode_len_no_pad(src.len()))?;
+    // Safety: decoding only writes bytes to dest
+    decode_iter_with_alphabet_uninit(src, unsafe { as_uninit(dest) }, alphabet)
+}
+
+/// [`decode_iter_with_alphabet`] into a possibly uninitialized dest, which must be long enough
+pub(crate) fn decode_iter_with_alphabet_uninit(
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    if src.is_empty() {
         return Ok(0);
     }
 
+    let [d0, d1, d2, _] = &alphabet.d;
     let src_chunks = src.chunks(32);
     let mut data_iter = src_chunks.zip(dest.chunks_mut(24)).peekable();
 
     let mut final_len = 0;
+    let mut src_i = 0;
 
     while let Some((src, dest)) = data_iter.next() {
         if data_iter.peek().is_none() {
-            // Remove padding (if exists)
-            let src = match &src[..] {
-                [rest @ .., b'=', b'='] => rest,
-                [rest @ .., b'='] => rest,
-                _ => &src,
-            };
             let leftover = src.len() % 4;
+            if leftover == 1 {
+                return Err(CodecError::InputModError(src_i + src.len()));
+            }
             let len_nopad = match leftover {
                 0 => src.len(),
                 _ => (src.len() / 4) * 4,
@@ -290,26 +584,32 @@ pub fn decode_iter(src: &[u8], dest: &mut [u8]) -> Result<usize, CodecError> {
 
             let mut dest_i;
             unsafe {
-                dest_i = decode_any(src, dest)?;
+                dest_i = decode_any(src, dest, alphabet).map_err(|e| e.offset_by(src_i))?;
             }
 
             match leftover {
                 0 => (),
                 2 => {
-                    let x = D0[src[len_nopad] as usize] | D1[src[len_nopad + 1] as usize];
-                    dest[dest_i] = x as u8; // i.e. second char
+                    let x = d0[src[len_nopad] as usize] | d1[src[len_nopad + 1] as usize];
+                    if x >= BADCHAR {
+                        return Err(invalid_byte(&src[len_nopad..], src_i + len_nopad, alphabet));
+                    }
+                    dest[dest_i].write(x as u8); // i.e. second char
                     dest_i += 1;
                 }
                 3 => {
                     let y = &src[len_nopad..len_nopad + 3];
-                    let x: u32 = D0[y[0] as usize] | D1[y[1] as usize] | D2[y[2] as usize]; /* 0x3c */
+                    let x: u32 = d0[y[0] as usize] | d1[y[1] as usize] | d2[y[2] as usize]; /* 0x3c */
+                    if x >= BADCHAR {
+                        return Err(invalid_byte(y, src_i + len_nopad, alphabet));
+                    }
 
                     let x0: *const u8 = &x as *const u32 as *const u8;
                     let x1 = unsafe { x0.offset(1) };
 
                     unsafe {
-                        dest[dest_i] = *x0;
-                        dest[dest_i + 1] = *x1;
+                        dest[dest_i].write(*x0);
+                        dest[dest_i + 1].write(*x1);
                     }
                     dest_i += 2;
                 }
@@ -321,9 +621,10 @@ pub fn decode_iter(src: &[u8], dest: &mut [u8]) -> Result<usize, CodecError> {
         }
 
         unsafe {
-            decode_32(&src, dest)?;
+            decode_32(src, dest, alphabet).map_err(|e| e.offset_by(src_i))?;
         }
         final_len += 24;
+        src_i += 32;
     }
 
     Ok(final_len)
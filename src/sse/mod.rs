@@ -0,0 +1,538 @@
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+use core::mem::MaybeUninit;
+
+use crate::alphabet::{Alphabet, Variant, STANDARD};
+use crate::avx2::COMPACT_LUT;
+use crate::{as_uninit, copy_to_uninit, CodecError, LineWrap};
+
+use super::simple;
+use super::simple::TranscodeTable;
+
+// 128-bit version of the avx2 module, for CPUs with SSSE3 but no AVX2.
+// The AVX2 code was translated from the SSE procedures of base64simd:
+// https://github.com/WojciechMula/base64simd
+// See the avx2 module for the license.
+//
+// Only SSSE3 is required: SSE4.1's ptest and pblendvb are replaced by SSE2 equivalents.
+
+#[inline(always)]
+unsafe fn enc_reshuffle(input: __m128i) -> __m128i {
+    // https://github.com/WojciechMula/base64simd/blob/master/encode/unpack_bigendian.cpp
+    let input: __m128i = _mm_shuffle_epi8(
+        input,
+        _mm_set_epi8(10, 11, 9, 10, 7, 8, 6, 7, 4, 5, 3, 4, 1, 2, 0, 1),
+    );
+
+    let t0: __m128i = _mm_and_si128(input, _mm_set1_epi32(0x0fc0fc00));
+    let t1: __m128i = _mm_mulhi_epu16(t0, _mm_set1_epi32(0x04000040));
+
+    let t2 = _mm_and_si128(input, _mm_set1_epi32(0x003f03f0));
+    let t3 = _mm_mullo_epi16(t2, _mm_set1_epi32(0x01000010));
+
+    _mm_or_si128(t1, t3)
+}
+
+/// Translation from 6 bit values to chars in the encode loop
+enum EncodeLut {
+    /// Offsets added to each 6 bit value, selected by range:
+    /// A..Z, a..z, 0..9 (x10), then the two alphabet specific chars
+    Offsets(__m128i),
+    /// Direct lookup of any alphabet, one table per 16 values
+    Nibbles([__m128i; 4]),
+}
+
+#[inline(always)]
+unsafe fn enc_lut(alphabet: &Alphabet) -> EncodeLut {
+    match alphabet.variant {
+        Variant::Standard => EncodeLut::Offsets(_mm_setr_epi8(
+            65, 71, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -19, -16, 0, 0,
+        )),
+        Variant::UrlSafe => EncodeLut::Offsets(_mm_setr_epi8(
+            65, 71, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -17, 32, 0, 0,
+        )),
+        Variant::Custom => EncodeLut::Nibbles(alphabet.nibble_encode.map(|t| load_table(&t))),
+    }
+}
+
+#[inline(always)]
+unsafe fn load_table(table: &[u8; 16]) -> __m128i {
+    _mm_loadu_si128(table.as_ptr() as *const __m128i)
+}
+
+#[inline(always)]
+unsafe fn enc_translate(input: __m128i, lut: &EncodeLut) -> __m128i {
+    match lut {
+        EncodeLut::Offsets(lut) => {
+            let mut indices = _mm_subs_epu8(input, _mm_set1_epi8(51));
+            let mask = _mm_cmpgt_epi8(input, _mm_set1_epi8(25));
+            indices = _mm_sub_epi8(indices, mask);
+
+            _mm_add_epi8(input, _mm_shuffle_epi8(*lut, indices))
+        }
+        EncodeLut::Nibbles(tables) => {
+            // pshufb only looks at the low nibble, so look up all four tables
+            // and keep the one selected by the high bits of each value
+            let hi = _mm_and_si128(_mm_srli_epi16(input, 4), _mm_set1_epi8(0x03));
+            let mut out = _mm_setzero_si128();
+            for (i, table) in tables.iter().enumerate() {
+                let select = _mm_cmpeq_epi8(hi, _mm_set1_epi8(i as i8));
+                out = _mm_or_si128(out, _mm_and_si128(select, _mm_shuffle_epi8(*table, input)));
+            }
+            out
+        }
+    }
+}
+
+/// Whether ssse3 can be used, always true when it's enabled at compile time
+#[cfg(target_feature = "ssse3")]
+#[inline(always)]
+pub fn has_ssse3() -> bool {
+    true
+}
+
+/// Whether ssse3 can be used, detected at runtime
+#[cfg(not(target_feature = "ssse3"))]
+#[inline]
+pub fn has_ssse3() -> bool {
+    #[cfg(feature = "std")]
+    {
+        is_x86_feature_detected!("ssse3")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        crate::detect::has_ssse3()
+    }
+}
+
+/// Encode a slice of bytes into base64 using ssse3 instructions and the standard alphabet,
+/// with padding
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+/// - dest must be at least `encode_len(str.len())` long
+#[target_feature(enable = "ssse3")]
+pub unsafe fn encode(dest: &mut [u8], str: &[u8]) -> usize {
+    let len = encode_with_alphabet(dest, str, &STANDARD);
+    simple::pad(dest, len)
+}
+
+/// Encode a slice of bytes into base64 using ssse3 instructions and the given alphabet,
+/// without padding
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+/// - dest must be at least `encode_len_no_pad(str.len())` long
+#[target_feature(enable = "ssse3")]
+pub unsafe fn encode_with_alphabet(dest: &mut [u8], str: &[u8], alphabet: &Alphabet) -> usize {
+    encode_with_alphabet_uninit(as_uninit(dest), str, alphabet)
+}
+
+/// [`encode_with_alphabet`] into a possibly uninitialized dest
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+/// - dest must be at least `encode_len_no_pad(str.len())` long
+#[target_feature(enable = "ssse3")]
+pub(crate) unsafe fn encode_with_alphabet_uninit(
+    dest: &mut [MaybeUninit<u8>],
+    str: &[u8],
+    alphabet: &Alphabet,
+) -> usize {
+    let lut = enc_lut(alphabet);
+    let mut str_offset = 0;
+    let mut dest_offset = 0;
+
+    // Each 16 byte load encodes its first 12 bytes
+    while str.len() - str_offset >= 16 {
+        let inputvector = _mm_loadu_si128(str.as_ptr().add(str_offset) as *const __m128i);
+        let outputvector = enc_translate(enc_reshuffle(inputvector), &lut);
+        _mm_storeu_si128(
+            dest.as_mut_ptr().add(dest_offset) as *mut __m128i,
+            outputvector,
+        );
+        str_offset += 12;
+        dest_offset += 16;
+    }
+
+    dest_offset
+        + simple::encode_with_alphabet_uninit(
+            &str[str_offset..],
+            &mut dest[dest_offset..],
+            alphabet,
+        )
+}
+
+/// Encode a slice of bytes into line wrapped base64 using ssse3 instructions, without padding
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+/// - dest must be at least `wrap.wrapped_len(encode_len_no_pad(str.len()))` long
+#[target_feature(enable = "ssse3")]
+pub unsafe fn encode_wrapped(
+    dest: &mut [u8],
+    str: &[u8],
+    alphabet: &Alphabet,
+    wrap: LineWrap,
+) -> usize {
+    encode_wrapped_uninit(as_uninit(dest), str, alphabet, wrap)
+}
+
+/// [`encode_wrapped`] into a possibly uninitialized dest
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+/// - dest must be at least `wrap.wrapped_len(encode_len_no_pad(str.len()))` long
+#[target_feature(enable = "ssse3")]
+pub(crate) unsafe fn encode_wrapped_uninit(
+    dest: &mut [MaybeUninit<u8>],
+    str: &[u8],
+    alphabet: &Alphabet,
+    wrap: LineWrap,
+) -> usize {
+    let lut = enc_lut(alphabet);
+    let ending = wrap.ending().as_bytes();
+    let line_groups = wrap.line_len() / 4;
+
+    let mut str_offset = 0;
+    let mut dest_offset = 0;
+    // Groups of 4 chars written to the current line
+    let mut line_pos = 0;
+
+    while str.len() - str_offset >= 16 {
+        let inputvector = _mm_loadu_si128(str.as_ptr().add(str_offset) as *const __m128i);
+        let outputvector = enc_translate(enc_reshuffle(inputvector), &lut);
+        _mm_storeu_si128(
+            dest.as_mut_ptr().add(dest_offset) as *mut __m128i,
+            outputvector,
+        );
+
+        // Only keep the groups that fit on the current line,
+        // anything past them is overwritten by the line ending and the next store
+        let groups = (line_groups - line_pos).min(4);
+        str_offset += groups * 3;
+        dest_offset += groups * 4;
+        line_pos += groups;
+
+        if line_pos == line_groups && str_offset < str.len() {
+            copy_to_uninit(&mut dest[dest_offset..], ending);
+            dest_offset += ending.len();
+            line_pos = 0;
+        }
+    }
+
+    dest_offset
+        + simple::encode_wrapped_from(
+            &str[str_offset..],
+            &mut dest[dest_offset..],
+            alphabet,
+            wrap,
+            line_pos * 4,
+        )
+}
+
+#[inline(always)]
+unsafe fn dec_reshuffle(input: __m128i) -> __m128i {
+    // pack_madd, as in the avx2 module
+    let merge_ab_and_bc: __m128i = _mm_maddubs_epi16(input, _mm_set1_epi32(0x01400140));
+    let out: __m128i = _mm_madd_epi16(merge_ab_and_bc, _mm_set1_epi32(0x00011000));
+
+    // Pack bytes together within 32-bit words, discarding word 3
+    _mm_shuffle_epi8(
+        out,
+        _mm_setr_epi8(2, 1, 0, 6, 5, 4, 10, 9, 8, 14, 13, 12, -1, -1, -1, -1),
+    )
+}
+
+/// Copy a slice to dest, skipping spaces, tabs, CR and LF, using ssse3 instructions
+/// dest must be at least as long as str
+/// Returns the number of bytes written to dest
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+#[target_feature(enable = "ssse3")]
+pub unsafe fn compact_whitespace(dest: &mut [u8], str: &[u8]) -> usize {
+    assert!(dest.len() >= str.len());
+    let mut str_i = 0;
+    let mut dest_i = 0;
+
+    // dest_i <= str_i, so there is always room for a full store
+    while str.len() - str_i >= 16 {
+        let input = _mm_loadu_si128(str.as_ptr().add(str_i) as *const __m128i);
+        let whitespace = _mm_or_si128(
+            _mm_or_si128(
+                _mm_cmpeq_epi8(input, _mm_set1_epi8(b' ' as i8)),
+                _mm_cmpeq_epi8(input, _mm_set1_epi8(b'\t' as i8)),
+            ),
+            _mm_or_si128(
+                _mm_cmpeq_epi8(input, _mm_set1_epi8(b'\r' as i8)),
+                _mm_cmpeq_epi8(input, _mm_set1_epi8(b'\n' as i8)),
+            ),
+        );
+        let mask = _mm_movemask_epi8(whitespace) as u32;
+
+        if mask == 0 {
+            _mm_storeu_si128(dest.as_mut_ptr().add(dest_i) as *mut __m128i, input);
+            dest_i += 16;
+        } else {
+            // Compact each 8 byte piece with a lookup table
+            for piece in 0..2 {
+                let piece_mask = (mask >> (piece * 8)) as u8;
+                let bytes = _mm_loadl_epi64(str.as_ptr().add(str_i + piece * 8) as *const __m128i);
+                let shuffle =
+                    _mm_loadl_epi64(COMPACT_LUT[piece_mask as usize].as_ptr() as *const __m128i);
+                _mm_storel_epi64(
+                    dest.as_mut_ptr().add(dest_i) as *mut __m128i,
+                    _mm_shuffle_epi8(bytes, shuffle),
+                );
+                dest_i += 8 - piece_mask.count_ones() as usize;
+            }
+        }
+        str_i += 16;
+    }
+
+    dest_i + simple::compact_whitespace(&str[str_i..], &mut dest[dest_i..])
+}
+
+/// Lookup tables used to validate and translate chars in the decode loop
+enum DecodeLuts {
+    /// Classify chars by nibble, then add an offset selected by the high nibble
+    Roll {
+        lut_lo: __m128i,
+        lut_hi: __m128i,
+        lut_roll: __m128i,
+        // The char that shares its high nibble with other valid chars,
+        // and the adjustment applied to its lut_roll index
+        special: __m128i,
+        special_adjust: __m128i,
+    },
+    /// Direct lookup of any ASCII alphabet, one table per high nibble.
+    /// Invalid chars map to 0xff.
+    Nibbles([__m128i; 8]),
+}
+
+#[inline(always)]
+unsafe fn dec_luts(alphabet: &Alphabet) -> DecodeLuts {
+    // Same tables as the avx2 module, see there for how they're built
+    match alphabet.variant {
+        Variant::Standard => DecodeLuts::Roll {
+            lut_lo: _mm_setr_epi8(
+                0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x1A, 0x1B, 0x1B,
+                0x1B, 0x1A,
+            ),
+            lut_hi: _mm_setr_epi8(
+                0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x08, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+                0x10, 0x10,
+            ),
+            lut_roll: _mm_setr_epi8(0, 16, 19, 4, -65, -65, -71, -71, 0, 0, 0, 0, 0, 0, 0, 0),
+            special: _mm_set1_epi8(0x2f),
+            special_adjust: _mm_set1_epi8(-1),
+        },
+        Variant::UrlSafe => DecodeLuts::Roll {
+            lut_lo: _mm_setr_epi8(
+                0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x3B, 0x3B, 0x3A,
+                0x3B, 0x33,
+            ),
+            lut_hi: _mm_setr_epi8(
+                0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x20, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+                0x10, 0x10,
+            ),
+            lut_roll: _mm_setr_epi8(0, 0, 17, 4, -65, -65, -71, -71, -32, 0, 0, 0, 0, 0, 0, 0),
+            special: _mm_set1_epi8(0x5f),
+            special_adjust: _mm_set1_epi8(3),
+        },
+        Variant::Custom => DecodeLuts::Nibbles(alphabet.nibble_decode.map(|t| load_table(&t))),
+    }
+}
+
+/// Translate 16 chars to their 6 bit values,
+/// or return a mask with a bit set for each byte of `str` that is not part of the alphabet
+#[inline(always)]
+unsafe fn dec_translate(str: __m128i, luts: &DecodeLuts) -> Result<__m128i, u32> {
+    match luts {
+        DecodeLuts::Roll {
+            lut_lo,
+            lut_hi,
+            lut_roll,
+            special,
+            special_adjust,
+        } => {
+            let mask_2f: __m128i = _mm_set1_epi8(0x2f);
+
+            // lookup
+            let hi_nibbles: __m128i = _mm_srli_epi32(str, 4);
+            let lo_nibbles: __m128i = _mm_and_si128(str, mask_2f);
+
+            let lo: __m128i = _mm_shuffle_epi8(*lut_lo, lo_nibbles);
+            let eq_special: __m128i = _mm_cmpeq_epi8(str, *special);
+            let eq_special = _mm_and_si128(eq_special, *special_adjust);
+
+            let hi_nibbles = _mm_and_si128(hi_nibbles, mask_2f);
+            let hi: __m128i = _mm_shuffle_epi8(*lut_hi, hi_nibbles);
+            let roll: __m128i = _mm_shuffle_epi8(*lut_roll, _mm_add_epi8(eq_special, hi_nibbles));
+
+            // A char is invalid if its nibbles share a class bit
+            let valid = _mm_cmpeq_epi8(_mm_and_si128(lo, hi), _mm_setzero_si128());
+            let invalid = !_mm_movemask_epi8(valid) as u32 & 0xffff;
+            if invalid != 0 {
+                return Err(invalid);
+            }
+
+            Ok(_mm_add_epi8(str, roll))
+        }
+        DecodeLuts::Nibbles(tables) => {
+            // Chars >= 0x80 match none of the tables and stay invalid
+            let hi = _mm_and_si128(_mm_srli_epi16(str, 4), _mm_set1_epi8(0x0f));
+            let mut out = _mm_set1_epi8(-1);
+            for (i, table) in tables.iter().enumerate() {
+                let select = _mm_cmpeq_epi8(hi, _mm_set1_epi8(i as i8));
+                out = _mm_or_si128(
+                    _mm_andnot_si128(select, out),
+                    _mm_and_si128(select, _mm_shuffle_epi8(*table, str)),
+                );
+            }
+
+            let invalid = _mm_movemask_epi8(out) as u32;
+            if invalid != 0 {
+                return Err(invalid);
+            }
+
+            Ok(out)
+        }
+    }
+}
+
+/// Decode a slice of base64 using ssse3 instructions and the standard alphabet
+/// Up to two padding chars are accepted, and the input length must be a multiple of 4
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+/// - out must be at least `decode_len_no_pad(src.len())` long, without counting padding
+#[target_feature(enable = "ssse3")]
+pub unsafe fn decode(out: &mut [u8], src: &[u8]) -> Result<usize, CodecError> {
+    decode_with_alphabet(out, simple::unpad(src)?, &STANDARD)
+}
+
+/// Decode a slice of unpadded base64 using ssse3 instructions and the given alphabet.
+/// An invalid char is reported with its offset as soon as the vector containing it is decoded.
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+/// - out must be at least `decode_len_no_pad(src.len())` long
+#[target_feature(enable = "ssse3")]
+pub unsafe fn decode_with_alphabet(
+    out: &mut [u8],
+    src: &[u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    decode_with_alphabet_uninit(as_uninit(out), src, alphabet)
+}
+
+/// [`decode_with_alphabet`] into a possibly uninitialized output
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+/// - out must be at least `decode_len_no_pad(src.len())` long
+#[target_feature(enable = "ssse3")]
+pub(crate) unsafe fn decode_with_alphabet_uninit(
+    out: &mut [MaybeUninit<u8>],
+    src: &[u8],
+    alphabet: &Alphabet,
+) -> Result<usize, CodecError> {
+    // Checked first, like simple does, so the error doesn't depend on the input's content
+    if src.len() % 4 == 1 {
+        return Err(CodecError::InputModError(src.len()));
+    }
+
+    let luts = dec_luts(alphabet);
+    let mut src_i = 0;
+    let mut dest_i = 0;
+
+    // 16 chars decode to 12 bytes but 16 are stored,
+    // so leave enough input for the store to fit in the output
+    while src.len() - src_i >= 24 {
+        let str = _mm_loadu_si128(src.as_ptr().add(src_i) as *const __m128i);
+
+        let str = dec_translate(str, &luts).map_err(|invalid| invalid_byte(src, src_i, invalid))?;
+
+        src_i += 16;
+
+        // Reshuffle the input to packed 12-byte output format:
+        let str = dec_reshuffle(str);
+        _mm_storeu_si128(out.as_mut_ptr().add(dest_i) as *mut __m128i, str);
+        dest_i += 12;
+    }
+
+    let end_decode_len =
+        simple::decode_with_alphabet_uninit(&src[src_i..], &mut out[dest_i..], alphabet)
+            .map_err(|e| e.offset_by(src_i))?;
+    Ok(dest_i + end_decode_len)
+}
+
+/// Check that every char of unpadded `src` is part of the alphabet using ssse3 instructions,
+/// without decoding it. Its length isn't checked.
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+#[target_feature(enable = "ssse3")]
+pub unsafe fn validate(src: &[u8], alphabet: &Alphabet) -> Result<(), CodecError> {
+    let luts = dec_luts(alphabet);
+    let mut src_i = 0;
+
+    while src.len() - src_i >= 16 {
+        let str = _mm_loadu_si128(src.as_ptr().add(src_i) as *const __m128i);
+        dec_translate(str, &luts).map_err(|invalid| invalid_byte(src, src_i, invalid))?;
+        src_i += 16;
+    }
+
+    // The tail, which simple reports with the offset
+    simple::validate_with_alphabet(&src[src_i..], alphabet).map_err(|e| e.offset_by(src_i))
+}
+
+/// Convert the chars of unpadded `buf` from one alphabet to another in place using ssse3
+/// instructions, checking that they're part of `from`. Its length isn't checked.
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+#[target_feature(enable = "ssse3")]
+pub unsafe fn transcode(buf: &mut [u8], from: &Alphabet, to: &Alphabet) -> Result<(), CodecError> {
+    transcode_with_table(buf, from, to, &TranscodeTable::new(from, to))
+}
+
+/// [`transcode`] with the table of the scalar tail built by the caller
+///
+/// # Safety
+/// - Must only be executed on ssse3 enabled cpus
+#[target_feature(enable = "ssse3")]
+pub(crate) unsafe fn transcode_with_table(
+    buf: &mut [u8],
+    from: &Alphabet,
+    to: &Alphabet,
+    table: &TranscodeTable,
+) -> Result<(), CodecError> {
+    let luts = dec_luts(from);
+    let lut = enc_lut(to);
+    let mut i = 0;
+
+    // Chars are translated to their values and straight back to chars of the other alphabet
+    while buf.len() - i >= 16 {
+        let ptr = buf.as_mut_ptr().add(i) as *mut __m128i;
+        let values = dec_translate(_mm_loadu_si128(ptr), &luts)
+            .map_err(|invalid| invalid_byte(buf, i, invalid))?;
+        _mm_storeu_si128(ptr, enc_translate(values, &lut));
+        i += 16;
+    }
+
+    // The tail, which simple reports with the offset
+    simple::transcode_with_table(&mut buf[i..], from, table).map_err(|e| e.offset_by(i))
+}
+
+/// Error for the first invalid byte of the 16 bytes at `src[offset..]`
+#[cold]
+fn invalid_byte(src: &[u8], offset: usize, invalid: u32) -> CodecError {
+    let offset = offset + invalid.trailing_zeros() as usize;
+    CodecError::InvalidByte {
+        offset,
+        byte: src[offset],
+    }
+}
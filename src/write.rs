@@ -0,0 +1,129 @@
+use std::io::{self, Write};
+
+use crate::{EncodeOptions, Encoder};
+
+/// Input bytes encoded per block, a multiple of 3
+const BLOCK_LEN: usize = 3 * 8 * 1024;
+
+/// Encodes everything written to it as base64 and writes it to the inner writer.
+///
+/// This is an [`Encoder`] writing to the inner writer, so partial 3 byte groups are kept
+/// between writes, and the final group and padding are written by [`EncoderWriter::finish`],
+/// or when the writer is dropped.
+/// Errors are ignored on drop, so call `finish` to handle them.
+///
+/// ```
+/// use std::io::Write;
+/// use bs64::{EncodeOptions, EncoderWriter};
+///
+/// let mut writer = EncoderWriter::new(Vec::new(), EncodeOptions::default());
+/// writer.write_all(b"hel").unwrap();
+/// writer.write_all(b"lo").unwrap();
+/// let output = writer.finish().unwrap();
+/// assert_eq!(output, b"aGVsbG8=");
+/// ```
+pub struct EncoderWriter<'a, W: Write> {
+    encoder: Encoder<'a>,
+    /// None once finished
+    inner: Option<W>,
+    /// Encoded output, of which buf[buf_pos..buf_len] is not written yet
+    buf: Vec<u8>,
+    buf_pos: usize,
+    buf_len: usize,
+    /// Set while the inner writer is called, to skip finishing on drop if it panicked
+    panicked: bool,
+}
+
+impl<'a, W: Write> EncoderWriter<'a, W> {
+    pub fn new(inner: W, options: EncodeOptions<'a>) -> Self {
+        let encoder = Encoder::new(options);
+        // Each write encodes at most one block, along with up to 2 bytes left from the last one
+        let buf_len = encoder.update_len(BLOCK_LEN + 2);
+        EncoderWriter {
+            encoder,
+            inner: Some(inner),
+            buf: vec![0; buf_len],
+            buf_pos: 0,
+            buf_len: 0,
+            panicked: false,
+        }
+    }
+
+    /// Write the final group and padding, flush, and return the inner writer
+    ///
+    /// # Panics
+    /// If the writer was already finished
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_final()?;
+        Ok(self.inner.take().expect("writer already finished"))
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("writer already finished")
+    }
+
+    /// Writing to the inner writer directly will corrupt the output
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer already finished")
+    }
+
+    fn write_final(&mut self) -> io::Result<()> {
+        self.write_buf()?;
+        self.buf_len = self.encoder.finalize(&mut self.buf)?;
+        self.write_buf()?;
+        self.panicked = true;
+        let result = self.get_mut().flush();
+        self.panicked = false;
+        result
+    }
+
+    /// Write all buffered output to the inner writer
+    fn write_buf(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().expect("writer already finished");
+        while self.buf_pos < self.buf_len {
+            self.panicked = true;
+            let result = inner.write(&self.buf[self.buf_pos..self.buf_len]);
+            self.panicked = false;
+            match result {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.buf_pos += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        self.buf_pos = 0;
+        self.buf_len = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncoderWriter<'_, W> {
+    fn write(&mut self, input: &[u8]) -> io::Result<usize> {
+        self.write_buf()?;
+        if input.is_empty() {
+            return Ok(0);
+        }
+
+        let len = input.len().min(BLOCK_LEN);
+        self.buf_len = self.encoder.update(&input[..len], &mut self.buf)?;
+        Ok(len)
+    }
+
+    /// Write the buffered output and flush the inner writer.
+    /// A partial group is kept until more input is written, or the writer is finished.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buf()?;
+        self.panicked = true;
+        let result = self.get_mut().flush();
+        self.panicked = false;
+        result
+    }
+}
+
+impl<W: Write> Drop for EncoderWriter<'_, W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() && !self.panicked {
+            let _ = self.write_final();
+        }
+    }
+}
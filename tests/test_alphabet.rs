@@ -0,0 +1,144 @@
+use bs64::alphabet::{STANDARD, URL_SAFE};
+use bs64::{Alphabet, CodecError};
+#[cfg(feature = "alloc")]
+use bs64::{DecodeOptions, EncodeOptions};
+#[cfg(feature = "alloc")]
+use data_encoding::{BASE64URL, BASE64URL_NOPAD};
+#[cfg(feature = "alloc")]
+use rand::prelude::*;
+
+#[test]
+#[cfg(feature = "alloc")]
+fn url_safe_length_to_1000() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    for i in 0..1000 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+
+        let expected = BASE64URL.encode(&input);
+        let output = EncodeOptions::default().alphabet(&URL_SAFE).encode(&input);
+        assert_eq!(output, expected);
+
+        let mut simple_output = vec![0u8; bs64::encode_len_no_pad(i)];
+        bs64::simple::encode_with_alphabet(&input, &mut simple_output, &URL_SAFE).unwrap();
+        assert_eq!(simple_output, BASE64URL_NOPAD.encode(&input).as_bytes());
+
+        let decoded = DecodeOptions::default()
+            .alphabet(&URL_SAFE)
+            .decode(output.as_bytes())
+            .unwrap();
+        assert_eq!(decoded, input);
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn alphabets_reject_each_others_chars() {
+    let standard = b"+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/+/";
+    let url_safe = b"-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_-_";
+
+    let options = DecodeOptions::default().alphabet(&URL_SAFE);
+    assert!(options.decode(standard).is_err());
+    assert!(options.decode(url_safe).is_ok());
+
+    let options = DecodeOptions::default().alphabet(&STANDARD);
+    assert!(options.decode(url_safe).is_err());
+    assert!(options.decode(standard).is_ok());
+}
+
+#[cfg(target_arch = "x86_64")]
+type DecodeFn = unsafe fn(&mut [u8], &[u8], &Alphabet) -> Result<usize, CodecError>;
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn simd_classifies_every_byte() {
+    let mut decoders: Vec<(&str, DecodeFn)> = Vec::new();
+    if is_x86_feature_detected!("avx2") {
+        decoders.push(("avx2", bs64::avx2::decode_with_alphabet));
+    }
+    if is_x86_feature_detected!("ssse3") {
+        decoders.push(("sse", bs64::sse::decode_with_alphabet));
+    }
+    let custom =
+        Alphabet::new(b"zyxwvutsrqponmlkjihgfedcba9876543210ZYXWVUTSRQPONMLKJIHGFEDCBA~!").unwrap();
+    for alphabet in [&STANDARD, &URL_SAFE, &custom] {
+        // 64 valid chars so the vector loop runs, with one byte replaced
+        let valid: Vec<u8> = alphabet.chars().to_vec();
+        // The vector loop covers the first 32 chars, the rest is the tail
+        for pos in [0, 17, 31, 40, 63] {
+            for byte in 0..=255u8 {
+                let mut input = valid.clone();
+                input[pos] = byte;
+
+                let mut simple_out = vec![0u8; 48];
+                let simple = bs64::simple::decode_with_alphabet(&input, &mut simple_out, alphabet);
+                for &(name, decode) in &decoders {
+                    let mut simd_out = vec![0u8; 48];
+                    let simd = unsafe { decode(&mut simd_out, &input, alphabet) };
+
+                    match (&simple, simd) {
+                        (Ok(_), Ok(_)) => {
+                            assert_eq!(simple_out, simd_out, "{name}: byte {byte} at {pos}")
+                        }
+                        (Err(e), Err(f)) => {
+                            assert!(
+                                matches!(e, CodecError::InvalidByte { offset, byte: b } if *offset == pos && *b == byte)
+                            );
+                            assert!(
+                                matches!(f, CodecError::InvalidByte { offset, byte: b } if offset == pos && b == byte),
+                                "{name}: byte {byte} at {pos}"
+                            );
+                        }
+                        _ => panic!("{name}: byte {byte} at {pos}: backends disagree"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn custom_alphabet_length_to_1000() {
+    let chars = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let alphabet = Alphabet::new(chars).unwrap();
+
+    let mut spec = data_encoding::Specification::new();
+    spec.symbols = String::from_utf8(chars.to_vec()).unwrap();
+    spec.padding = Some('=');
+    let reference = spec.encoding().unwrap();
+
+    let mut rng = SmallRng::seed_from_u64(20);
+    for i in 0..1000 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+
+        let expected = reference.encode(&input);
+        let output = EncodeOptions::default().alphabet(&alphabet).encode(&input);
+        assert_eq!(output, expected);
+
+        let decoded = DecodeOptions::default()
+            .alphabet(&alphabet)
+            .decode(output.as_bytes())
+            .unwrap();
+        assert_eq!(decoded, input);
+    }
+}
+
+#[test]
+fn invalid_alphabets_are_rejected() {
+    let mut chars = *STANDARD.chars();
+    chars[10] = b'A';
+    assert!(matches!(
+        Alphabet::new(&chars),
+        Err(CodecError::InvalidAlphabet(10, b'A'))
+    ));
+
+    chars[10] = b'=';
+    assert!(Alphabet::new(&chars).is_err());
+
+    chars[10] = 0xc3;
+    assert!(Alphabet::new(&chars).is_err());
+
+    assert_eq!(Alphabet::new(URL_SAFE.chars()).unwrap(), URL_SAFE);
+}
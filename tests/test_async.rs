@@ -0,0 +1,291 @@
+#![cfg(any(feature = "tokio", feature = "futures-io"))]
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use bs64::{
+    AsyncDecoderReader, AsyncEncoderWriter, CodecError, DecodeOptions, EncodeOptions, LineWrap,
+};
+use rand::prelude::*;
+
+/// Reads up to 100 bytes and writes up to 7 bytes at a time,
+/// returning Pending on every other call
+#[derive(Default)]
+struct Mock {
+    data: Vec<u8>,
+    pos: usize,
+    pending: bool,
+    closed: bool,
+}
+
+impl Mock {
+    fn reader(data: &[u8]) -> Self {
+        Mock {
+            data: data.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.pending = !self.pending;
+        if self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let len = buf.len().min(self.data.len() - self.pos).min(100);
+        buf[..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+        self.pos += len;
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        assert!(!self.closed, "write after close");
+        self.pending = !self.pending;
+        if self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let len = buf.len().min(7);
+        self.data.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+}
+
+/// Poll until ready, the mock is always ready again on the next poll
+fn block_on<T>(mut poll: impl FnMut(&mut Context<'_>) -> Poll<T>) -> T {
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(value) = poll(&mut cx) {
+            return value;
+        }
+    }
+}
+
+fn test_inputs() -> Vec<Vec<u8>> {
+    let mut rng = SmallRng::seed_from_u64(20);
+    [0, 1, 2, 3, 100, 1000, 100000]
+        .into_iter()
+        .map(|len| {
+            let mut input = vec![0u8; len];
+            rng.fill(&mut input[..]);
+            input
+        })
+        .collect()
+}
+
+fn options() -> [(EncodeOptions<'static>, DecodeOptions<'static>); 2] {
+    [
+        (EncodeOptions::default(), DecodeOptions::default()),
+        (
+            EncodeOptions::default().line_wrap(LineWrap::MIME),
+            DecodeOptions::default().ignore_whitespace(true),
+        ),
+    ]
+}
+
+fn codec_error(e: io::Error) -> CodecError {
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    *e.into_inner().unwrap().downcast::<CodecError>().unwrap()
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_io {
+    use super::*;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    impl AsyncRead for Mock {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let len = std::task::ready!(Mock::poll_read(
+                self.get_mut(),
+                cx,
+                buf.initialize_unfilled()
+            ))?;
+            buf.advance(len);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncWrite for Mock {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Mock::poll_write(self.get_mut(), cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn encode(input: &[u8], options: EncodeOptions) -> Vec<u8> {
+        let mut writer = AsyncEncoderWriter::new(Mock::default(), options);
+        for chunk in input.chunks(1001) {
+            let mut chunk = chunk;
+            while !chunk.is_empty() {
+                let len = block_on(|cx| Pin::new(&mut writer).poll_write(cx, chunk)).unwrap();
+                chunk = &chunk[len..];
+            }
+            block_on(|cx| Pin::new(&mut writer).poll_flush(cx)).unwrap();
+        }
+        block_on(|cx| Pin::new(&mut writer).poll_shutdown(cx)).unwrap();
+        let inner = writer.into_inner();
+        assert!(inner.closed);
+        inner.data
+    }
+
+    fn decode(input: &[u8], options: DecodeOptions) -> Result<Vec<u8>, CodecError> {
+        let mut reader = AsyncDecoderReader::new(Mock::reader(input), options);
+        let mut output = Vec::new();
+        let mut buf = [0u8; 1000];
+        loop {
+            let mut read_buf = ReadBuf::new(&mut buf);
+            block_on(|cx| Pin::new(&mut reader).poll_read(cx, &mut read_buf))
+                .map_err(codec_error)?;
+            match read_buf.filled() {
+                [] => return Ok(output),
+                filled => output.extend_from_slice(filled),
+            }
+        }
+    }
+
+    #[test]
+    fn tokio_round_trip() {
+        for input in test_inputs() {
+            for (encoder, decoder) in options() {
+                let encoded = encode(&input, encoder);
+                assert_eq!(encoded, encoder.encode(&input).as_bytes());
+                assert_eq!(decode(&encoded, decoder).unwrap(), input);
+            }
+        }
+        assert!(matches!(
+            decode(b"aGVsbG8*", DecodeOptions::default()),
+            Err(CodecError::InvalidByte { offset: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn tokio_repeats_first_error() {
+        let mut reader =
+            AsyncDecoderReader::new(Mock::reader(b"QU*DQUJD"), DecodeOptions::default());
+        let mut buf = [0u8; 16];
+        for _ in 0..3 {
+            let mut read_buf = ReadBuf::new(&mut buf);
+            let err =
+                block_on(|cx| Pin::new(&mut reader).poll_read(cx, &mut read_buf)).unwrap_err();
+            assert!(matches!(
+                codec_error(err),
+                CodecError::InvalidByte {
+                    offset: 2,
+                    byte: b'*'
+                }
+            ));
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_io {
+    use super::*;
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    impl AsyncRead for Mock {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Mock::poll_read(self.get_mut(), cx, buf)
+        }
+    }
+
+    impl AsyncWrite for Mock {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Mock::poll_write(self.get_mut(), cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn encode(input: &[u8], options: EncodeOptions) -> Vec<u8> {
+        let mut writer = AsyncEncoderWriter::new(Mock::default(), options);
+        for chunk in input.chunks(1001) {
+            let mut chunk = chunk;
+            while !chunk.is_empty() {
+                let len = block_on(|cx| Pin::new(&mut writer).poll_write(cx, chunk)).unwrap();
+                chunk = &chunk[len..];
+            }
+            block_on(|cx| Pin::new(&mut writer).poll_flush(cx)).unwrap();
+        }
+        block_on(|cx| Pin::new(&mut writer).poll_close(cx)).unwrap();
+        let inner = writer.into_inner();
+        assert!(inner.closed);
+        inner.data
+    }
+
+    fn decode(input: &[u8], options: DecodeOptions) -> Result<Vec<u8>, CodecError> {
+        let mut reader = AsyncDecoderReader::new(Mock::reader(input), options);
+        let mut output = Vec::new();
+        let mut buf = [0u8; 1000];
+        loop {
+            match block_on(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf)) {
+                Ok(0) => return Ok(output),
+                Ok(len) => output.extend_from_slice(&buf[..len]),
+                Err(e) => return Err(codec_error(e)),
+            }
+        }
+    }
+
+    #[test]
+    fn futures_io_round_trip() {
+        for input in test_inputs() {
+            for (encoder, decoder) in options() {
+                let encoded = encode(&input, encoder);
+                assert_eq!(encoded, encoder.encode(&input).as_bytes());
+                assert_eq!(decode(&encoded, decoder).unwrap(), input);
+            }
+        }
+        assert!(matches!(
+            decode(b"aGVsbG8*", DecodeOptions::default()),
+            Err(CodecError::InvalidByte { offset: 7, .. })
+        ));
+    }
+    #[test]
+    fn futures_io_repeats_first_error() {
+        let mut reader =
+            AsyncDecoderReader::new(Mock::reader(b"QU*DQUJD"), DecodeOptions::default());
+        let mut buf = [0u8; 16];
+        for _ in 0..3 {
+            let err = block_on(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf)).unwrap_err();
+            assert!(matches!(
+                codec_error(err),
+                CodecError::InvalidByte {
+                    offset: 2,
+                    byte: b'*'
+                }
+            ));
+        }
+    }
+}
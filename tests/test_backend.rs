@@ -0,0 +1,268 @@
+#[cfg(feature = "alloc")]
+use bs64::alphabet::{STANDARD, URL_SAFE};
+use bs64::Backend;
+#[cfg(feature = "alloc")]
+use bs64::{Alphabet, CodecError, DecodeOptions, EncodeOptions, LineWrap, Padding};
+#[cfg(feature = "alloc")]
+use rand::prelude::*;
+
+#[cfg(feature = "alloc")]
+fn available_backends() -> impl Iterator<Item = Backend> {
+    Backend::ALL.iter().copied().filter(|b| b.is_available())
+}
+
+#[test]
+fn backend_names_round_trip() {
+    for &backend in Backend::ALL {
+        assert_eq!(Backend::from_name(backend.name()), Some(backend));
+        assert_eq!(
+            Backend::from_name(&backend.name().to_uppercase()),
+            Some(backend)
+        );
+    }
+    assert_eq!(Backend::from_name("sse9"), None);
+    assert!(Backend::detect().is_available());
+    assert_eq!(Backend::detect(), Backend::detect());
+}
+
+#[test]
+fn unavailable_backend_is_rejected() {
+    // Nothing to check on a CPU that supports every backend
+    for &backend in Backend::ALL.iter().filter(|b| !b.is_available()) {
+        assert!(matches!(
+            bs64::EncodeOptions::default().try_backend(backend),
+            Err(bs64::CodecError::UnavailableBackend(b)) if b == backend
+        ));
+        assert!(matches!(
+            bs64::DecodeOptions::default().try_backend(backend),
+            Err(bs64::CodecError::UnavailableBackend(b)) if b == backend
+        ));
+        let panic = std::panic::catch_unwind(|| bs64::DecodeOptions::default().backend(backend));
+        assert!(panic.is_err(), "{backend:?}");
+    }
+    for &backend in Backend::ALL.iter().filter(|b| b.is_available()) {
+        assert!(bs64::DecodeOptions::default().try_backend(backend).is_ok());
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn backends_match_scalar() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    for len in (0..200).chain([1000, 4099, 10000]) {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+
+        for (encoder, decoder) in [
+            (EncodeOptions::default(), DecodeOptions::default()),
+            (
+                EncodeOptions::default().alphabet(&URL_SAFE),
+                DecodeOptions::default().alphabet(&URL_SAFE),
+            ),
+            (
+                EncodeOptions::default().line_wrap(LineWrap::MIME),
+                DecodeOptions::default().ignore_whitespace(true),
+            ),
+        ] {
+            let expected = encoder.backend(Backend::Scalar).encode(&input);
+            let decoder_scalar = decoder.backend(Backend::Scalar);
+            assert_eq!(decoder_scalar.decode(expected.as_bytes()).unwrap(), input);
+
+            // Corrupt a char, the error must be the same for every backend
+            let mut invalid = expected.clone().into_bytes();
+            if !invalid.is_empty() {
+                let i = rng.gen_range(0..invalid.len());
+                invalid[i] = b'*';
+            }
+            let expected_err = format!("{:?}", decoder_scalar.decode(&invalid));
+
+            for backend in available_backends() {
+                let output = encoder.backend(backend).encode(&input);
+                assert_eq!(output, expected, "{backend:?}, len {len}");
+                let decoder = decoder.backend(backend);
+                assert_eq!(decoder.decode(output.as_bytes()).unwrap(), input);
+                assert_eq!(
+                    format!("{:?}", decoder.decode(&invalid)),
+                    expected_err,
+                    "{backend:?}, len {len}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn backends_reject_padding_inside_data() {
+    for backend in available_backends() {
+        let decoder = DecodeOptions::default().backend(backend);
+        assert!(decoder.decode(b"QQ==QUI=").is_err(), "{backend:?}");
+        let decoder = decoder.ignore_whitespace(true);
+        assert!(decoder.decode(b"QQ==\nQUI=").is_err(), "{backend:?}");
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn backends_report_first_invalid_byte() {
+    let mut input = vec![b'A'; 1 << 20];
+    input[700_001] = b'*';
+    input[700_002] = b'.';
+    input[900_000] = b'!';
+    for backend in available_backends() {
+        let decoder = DecodeOptions::default().backend(backend);
+        assert!(
+            matches!(
+                decoder.decode(&input),
+                Err(CodecError::InvalidByte {
+                    offset: 700_001,
+                    byte: b'*'
+                })
+            ),
+            "{backend:?}"
+        );
+        // The length is checked before the content
+        assert!(
+            matches!(
+                decoder.decode(&input[..input.len() - 3]),
+                Err(CodecError::InputModError(_))
+            ),
+            "{backend:?}"
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn validate_matches_decode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let decoders = [
+        DecodeOptions::default(),
+        DecodeOptions::default().padding(Padding::Forbidden),
+        DecodeOptions::default().alphabet(&URL_SAFE).canonical(true),
+        DecodeOptions::default().ignore_whitespace(true),
+    ];
+    for len in (0..200).chain([1000, 5000, 10000]) {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+        let encoded = EncodeOptions::default().encode(&input).into_bytes();
+        let wrapped = EncodeOptions::default()
+            .line_wrap(LineWrap::MIME)
+            .encode(&input)
+            .into_bytes();
+
+        for encoded in [encoded, wrapped] {
+            let mut invalid = encoded.clone();
+            if !invalid.is_empty() {
+                let i = rng.gen_range(0..invalid.len());
+                invalid[i] = rng.gen();
+            }
+            for input in [&encoded, &invalid] {
+                for decoder in decoders {
+                    let expected = format!("{:?}", decoder.decode(input).map(|_| ()));
+                    for backend in available_backends() {
+                        let validated = decoder.backend(backend).validate(input);
+                        assert_eq!(format!("{validated:?}"), expected, "{backend:?}, len {len}");
+                    }
+                }
+            }
+        }
+    }
+    assert!(bs64::validate(b"aGVsbG8=").is_ok());
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn transcode_matches_decode_encode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let custom =
+        Alphabet::new(b"zyxwvutsrqponmlkjihgfedcba9876543210ZYXWVUTSRQPONMLKJIHGFEDCBA~!").unwrap();
+    // The encoder producing the input, then the conversion
+    let conversions = [
+        (
+            EncodeOptions::default(),
+            DecodeOptions::default(),
+            EncodeOptions::default().alphabet(&URL_SAFE),
+        ),
+        (
+            EncodeOptions::default().alphabet(&URL_SAFE),
+            DecodeOptions::default()
+                .alphabet(&URL_SAFE)
+                .padding(Padding::Optional),
+            EncodeOptions::default().padding(false),
+        ),
+        (
+            EncodeOptions::default().padding(false),
+            DecodeOptions::default().padding(Padding::Forbidden),
+            EncodeOptions::default().alphabet(&custom),
+        ),
+        (
+            EncodeOptions::default().line_wrap(LineWrap::MIME),
+            DecodeOptions::default()
+                .ignore_whitespace(true)
+                .canonical(true),
+            EncodeOptions::default()
+                .alphabet(&URL_SAFE)
+                .line_wrap(LineWrap::PEM),
+        ),
+    ];
+    for len in (0..200).chain([1000, 5000, 10000]) {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+
+        for (input_encoder, decoder, encoder) in conversions {
+            let encoded = input_encoder.encode(&input).into_bytes();
+            let mut invalid = encoded.clone();
+            if !invalid.is_empty() {
+                let i = rng.gen_range(0..invalid.len());
+                invalid[i] = rng.gen();
+            }
+
+            for input in [&encoded, &invalid] {
+                let expected = decoder
+                    .decode(input)
+                    .map(|decoded| encoder.encode(&decoded));
+                let expected = format!("{expected:?}");
+                for backend in available_backends() {
+                    let decoder = decoder.backend(backend);
+                    let mut output = vec![0u8; input.len() * 2 + 4];
+                    let transcoded = decoder
+                        .transcode(input, encoder, &mut output)
+                        .map(|len| String::from_utf8(output[..len].to_vec()).unwrap());
+                    assert_eq!(
+                        format!("{transcoded:?}"),
+                        expected,
+                        "{backend:?}, len {len}"
+                    );
+
+                    let mut buf = input.clone();
+                    buf.resize(input.len() * 2 + 4, 0);
+                    let transcoded = decoder
+                        .transcode_in_place(&mut buf, input.len(), encoder)
+                        .map(|len| String::from_utf8(buf[..len].to_vec()).unwrap());
+                    assert_eq!(
+                        format!("{transcoded:?}"),
+                        expected,
+                        "{backend:?}, len {len}"
+                    );
+                }
+            }
+        }
+    }
+
+    let mut output = [0u8; 8];
+    let len = bs64::transcode(b"+/+/", &STANDARD, &URL_SAFE, &mut output).unwrap();
+    assert_eq!(&output[..len], b"-_-_");
+    let mut buf = *b"-_-_";
+    let len = bs64::transcode_in_place(&mut buf, 4, &URL_SAFE, &STANDARD).unwrap();
+    assert_eq!(&buf[..len], b"+/+/");
+
+    // Unused bits of the last char are cleared, like decoding and encoding again would
+    let len = bs64::transcode(b"QR==", &STANDARD, &URL_SAFE, &mut output).unwrap();
+    assert_eq!(&output[..len], b"QQ==");
+    let canonical = DecodeOptions::default().canonical(true);
+    assert!(matches!(
+        canonical.transcode(b"QR==", EncodeOptions::default(), &mut output),
+        Err(CodecError::TrailingBits(1))
+    ));
+}
@@ -0,0 +1,446 @@
+#![cfg(feature = "alloc")]
+
+use bs64::alphabet::{STANDARD, URL_SAFE};
+use bs64::{CodecError, DecodeOptions, EncodeOptions, LineEnding, LineWrap, Padding};
+use data_encoding::{BASE64, BASE64_NOPAD};
+use rand::prelude::*;
+
+#[test]
+fn no_pad_length_to_1000() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let encoder = EncodeOptions::default().padding(false);
+    for i in 0..1000 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+
+        let expected = BASE64_NOPAD.encode(&input);
+        let output = encoder.encode(&input);
+        assert_eq!(output, expected);
+        assert_eq!(encoder.encode_len(i), expected.len());
+
+        let mut output = vec![0u8; bs64::encode_len_no_pad(i)];
+        assert_eq!(
+            encoder.encode_mut(&input, &mut output).unwrap(),
+            output.len()
+        );
+
+        for padding in [Padding::Optional, Padding::Forbidden] {
+            let decoded = DecodeOptions::default()
+                .padding(padding)
+                .decode(&output)
+                .unwrap();
+            assert_eq!(decoded, input);
+        }
+
+        let decoded = DecodeOptions::default()
+            .padding(Padding::Optional)
+            .decode(BASE64.encode(&input).as_bytes())
+            .unwrap();
+        assert_eq!(decoded, input);
+    }
+}
+
+#[test]
+fn padding_policy() {
+    let required = DecodeOptions::default();
+    let optional = DecodeOptions::default().padding(Padding::Optional);
+    let forbidden = DecodeOptions::default().padding(Padding::Forbidden);
+
+    assert_eq!(required.decode(b"QUI=").unwrap(), b"AB");
+    assert_eq!(optional.decode(b"QUI=").unwrap(), b"AB");
+    assert!(matches!(
+        forbidden.decode(b"QUI="),
+        Err(CodecError::InvalidPadding(3))
+    ));
+
+    assert!(matches!(
+        required.decode(b"QUI"),
+        Err(CodecError::InvalidPadding(3))
+    ));
+    assert_eq!(optional.decode(b"QUI").unwrap(), b"AB");
+    assert_eq!(forbidden.decode(b"QUI").unwrap(), b"AB");
+
+    // Incomplete or excess padding is never valid
+    for options in [required, optional, forbidden] {
+        assert!(matches!(
+            options.decode(b"QQ="),
+            Err(CodecError::InvalidPadding(2))
+        ));
+        assert!(matches!(
+            options.decode(b"QUI=="),
+            Err(CodecError::InvalidPadding(3))
+        ));
+        assert!(options.decode(b"QQ==QUI=").is_err());
+        assert!(matches!(
+            options.decode(b"QUJDR"),
+            Err(CodecError::InputModError(5))
+        ));
+    }
+}
+
+fn wrap_lines(encoded: &str, line_len: usize, ending: &str) -> String {
+    let lines: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(line_len)
+        .map(|line| std::str::from_utf8(line).unwrap())
+        .collect();
+    lines.join(ending)
+}
+
+#[test]
+fn line_wrap_length_to_1000() {
+    let wraps = [
+        LineWrap::MIME,
+        LineWrap::PEM,
+        LineWrap::new(4, LineEnding::Lf),
+        LineWrap::new(8, LineEnding::CrLf),
+        LineWrap::new(36, LineEnding::Lf),
+        LineWrap::new(1024, LineEnding::CrLf),
+    ];
+    let mut rng = SmallRng::seed_from_u64(20);
+    for i in 0..1000 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+
+        for wrap in wraps {
+            let ending = std::str::from_utf8(wrap.ending().as_bytes()).unwrap();
+            for padding in [true, false] {
+                let unwrapped = match padding {
+                    true => BASE64.encode(&input),
+                    false => BASE64_NOPAD.encode(&input),
+                };
+                let expected = wrap_lines(&unwrapped, wrap.line_len(), ending);
+
+                let encoder = EncodeOptions::default().padding(padding).line_wrap(wrap);
+                assert_eq!(encoder.encode_len(i), expected.len());
+                assert_eq!(encoder.encode(&input), expected);
+
+                if !padding {
+                    let mut output = vec![0u8; expected.len()];
+                    let len =
+                        bs64::simple::encode_wrapped(&input, &mut output, &STANDARD, wrap).unwrap();
+                    assert_eq!(len, expected.len());
+                    assert_eq!(output, expected.as_bytes());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn ignore_whitespace_length_to_1000() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let decoder = DecodeOptions::default().ignore_whitespace(true);
+    for i in 0..1000 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+
+        let wrapped = EncodeOptions::default()
+            .line_wrap(LineWrap::MIME)
+            .encode(&input);
+        assert_eq!(decoder.decode(wrapped.as_bytes()).unwrap(), input);
+
+        // Whitespace at random positions, including inside the padding
+        let mut spaced = Vec::new();
+        for c in BASE64.encode(&input).bytes() {
+            while rng.gen_bool(0.2) {
+                spaced.push(*b" \t\r\n".choose(&mut rng).unwrap());
+            }
+            spaced.push(c);
+        }
+        spaced.extend_from_slice(b"\r\n");
+        assert_eq!(decoder.decode(&spaced).unwrap(), input);
+
+        let mut output = vec![0u8; decoder.decode_len(spaced.len())];
+        let len = decoder.decode_mut(&spaced, &mut output).unwrap();
+        assert_eq!(output[..len], input);
+    }
+}
+
+#[test]
+fn whitespace_is_rejected_by_default() {
+    assert!(bs64::decode(b"QUJD\nREVG").is_err());
+    assert!(bs64::decode(b"QUJDREVG\n").is_err());
+
+    let decoder = DecodeOptions::default().ignore_whitespace(true);
+    assert_eq!(decoder.decode(b"QUJD\nREVG\n").unwrap(), b"ABCDEF");
+    assert_eq!(decoder.decode(b" Q U I = \n").unwrap(), b"AB");
+    assert_eq!(decoder.decode(b" \n ").unwrap(), b"");
+    assert!(matches!(
+        decoder.decode(b"QUI=\n="),
+        Err(CodecError::InvalidPadding(3))
+    ));
+    assert!(decoder.decode(b"QQ==\nQUI=").is_err());
+}
+
+#[test]
+fn whitespace_padding_errors_have_input_offsets() {
+    let decoder = DecodeOptions::default().ignore_whitespace(true);
+    let forbidden = decoder.padding(Padding::Forbidden);
+    let cases: [(DecodeOptions, &[u8], CodecError); 5] = [
+        (forbidden, b" QQ==", CodecError::InvalidPadding(3)),
+        (decoder, b"QU I \n", CodecError::InvalidPadding(4)),
+        (decoder, b" QQ\n=\n=\n=", CodecError::InvalidPadding(4)),
+        (decoder, b" QUJDR \n", CodecError::InputModError(6)),
+        (decoder, b"QU\nJDR =\n", CodecError::InputModError(8)),
+    ];
+    for (options, input, expected) in cases {
+        let expected = format!("{expected:?}");
+        let name = String::from_utf8_lossy(input);
+        assert_eq!(
+            format!("{:?}", options.decode(input)),
+            format!("Err({expected})")
+        );
+        assert_eq!(
+            format!("{:?}", options.decoded_len(input)),
+            format!("Err({expected})")
+        );
+        assert_eq!(
+            format!("{:?}", options.validate(input)),
+            format!("Err({expected})")
+        );
+        let mut buf = input.to_vec();
+        let result = options.decode_in_place(&mut buf).map(|output| output.len());
+        assert_eq!(format!("{result:?}"), format!("Err({expected})"), "{name}");
+
+        let mut decoder = bs64::Decoder::new(options);
+        let mut output = [0u8; 8];
+        let mut len = 0;
+        for c in input.chunks(1) {
+            len += decoder.update(c, &mut output[len..]).unwrap();
+        }
+        let result = decoder.finalize(&mut output[len..]);
+        assert_eq!(format!("{result:?}"), format!("Err({expected})"), "{name}");
+    }
+}
+
+#[test]
+fn truncated_group_error_message() {
+    let err = DecodeOptions::default()
+        .ignore_whitespace(true)
+        .decode(b"QU\nJDR =\n")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "truncated base64 group ending at offset 8");
+
+    let err = DecodeOptions::default()
+        .padding(Padding::Optional)
+        .decode(b"QUJDR=")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "truncated base64 group ending at offset 6");
+}
+
+#[test]
+fn canonical_rejects_trailing_bits() {
+    let canonical = DecodeOptions::default().canonical(true);
+    assert_eq!(bs64::decode(b"QR==").unwrap(), b"A");
+    assert_eq!(canonical.decode(b"QQ==").unwrap(), b"A");
+    assert!(matches!(
+        canonical.decode(b"QR=="),
+        Err(CodecError::TrailingBits(1))
+    ));
+
+    assert_eq!(bs64::decode(b"QUJDREF=").unwrap(), b"ABCDA");
+    assert!(matches!(
+        canonical.decode(b"QUJDREF="),
+        Err(CodecError::TrailingBits(6))
+    ));
+
+    let canonical = canonical
+        .padding(Padding::Forbidden)
+        .ignore_whitespace(true);
+    assert!(matches!(
+        canonical.decode(b"QU\nJD\nREF\n"),
+        Err(CodecError::TrailingBits(8))
+    ));
+    assert_eq!(canonical.decode(b"QU\nJD\nREE\n").unwrap(), b"ABCDA");
+}
+
+#[test]
+fn canonical_optional_padding_requires_padding() {
+    for options in [
+        DecodeOptions::default()
+            .padding(Padding::Optional)
+            .canonical(true),
+        DecodeOptions::default()
+            .canonical(true)
+            .padding(Padding::Optional),
+    ] {
+        assert_eq!(options.decode(b"QQ==").unwrap(), b"A");
+        assert!(matches!(
+            options.decode(b"QQ"),
+            Err(CodecError::InvalidPadding(2))
+        ));
+        assert!(matches!(
+            options.validate(b"QUI"),
+            Err(CodecError::InvalidPadding(3))
+        ));
+    }
+}
+
+#[test]
+fn canonical_accepts_all_encoder_output() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let canonical = DecodeOptions::default().canonical(true);
+    for i in 0..200 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+        assert_eq!(
+            canonical.decode(bs64::encode(&input).as_bytes()).unwrap(),
+            input
+        );
+    }
+}
+
+#[test]
+fn invalid_byte_offset_ignores_whitespace() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let encoder = EncodeOptions::default().line_wrap(LineWrap::MIME);
+    let decoder = DecodeOptions::default().ignore_whitespace(true);
+    for i in [10, 100, 1000, 10000] {
+        let mut data = vec![0u8; i];
+        rng.fill(&mut data[..]);
+        let mut input = encoder.encode(&data).into_bytes();
+
+        for _ in 0..20 {
+            let j = rng.gen_range(0..input.len() - 4);
+            if input[j] == b'\r' || input[j] == b'\n' {
+                continue;
+            }
+            let c = input[j];
+            input[j] = b'*';
+            match decoder.decode(&input) {
+                Err(CodecError::InvalidByte { offset, byte }) => {
+                    assert_eq!((offset, byte), (j, b'*'), "length {i}")
+                }
+                other => panic!("length {i}: expected invalid byte at {j}, got {other:?}"),
+            }
+            input[j] = c;
+        }
+    }
+}
+
+#[test]
+fn decode_mut_into_exact_len() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let cases = [
+        (EncodeOptions::default(), DecodeOptions::default()),
+        (
+            EncodeOptions::default().padding(false),
+            DecodeOptions::default().padding(Padding::Forbidden),
+        ),
+        (
+            EncodeOptions::default().line_wrap(LineWrap::MIME),
+            DecodeOptions::default().ignore_whitespace(true),
+        ),
+    ];
+    for i in 0..1000 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+
+        for (encoder, decoder) in cases {
+            let encoded = encoder.encode(&input);
+            assert_eq!(decoder.decoded_len(encoded.as_bytes()).unwrap(), i);
+
+            let mut output = vec![0u8; i];
+            let len = decoder.decode_mut(encoded.as_bytes(), &mut output).unwrap();
+            assert_eq!(len, i);
+            assert_eq!(output, input);
+
+            if i > 0 {
+                assert!(matches!(
+                    decoder.decode_mut(encoded.as_bytes(), &mut output[1..]),
+                    Err(CodecError::OutputLengthTooShort(..))
+                ));
+            }
+        }
+    }
+
+    assert_eq!(bs64::decoded_len(b"QQ==").unwrap(), 1);
+    assert!(matches!(
+        bs64::decoded_len(b"QUJDR"),
+        Err(CodecError::InputModError(5))
+    ));
+    assert!(matches!(
+        bs64::decoded_len(b"QUJ"),
+        Err(CodecError::InvalidPadding(3))
+    ));
+}
+
+#[test]
+fn decode_in_place_matches_decode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let decoders = [
+        DecodeOptions::default(),
+        DecodeOptions::default().padding(Padding::Optional),
+        DecodeOptions::default().canonical(true),
+        DecodeOptions::default().ignore_whitespace(true),
+    ];
+    for len in (0..300).chain([3000, 3072, 5000, 100_000]) {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+        let encoded = BASE64.encode(&input);
+        let wrapped = wrap_lines(&encoded, 76, "\r\n");
+
+        for decoder in decoders {
+            for encoded in [encoded.as_bytes(), wrapped.as_bytes()] {
+                let mut buf = encoded.to_vec();
+                let expected = decoder.decode(encoded);
+                let decoded = decoder.decode_in_place(&mut buf).map(|d| d.to_vec());
+                assert_eq!(format!("{decoded:?}"), format!("{expected:?}"), "len {len}");
+
+                if !encoded.is_empty() {
+                    // The same errors, at the same offsets
+                    let mut invalid = encoded.to_vec();
+                    let i = rng.gen_range(0..invalid.len());
+                    invalid[i] = b'*';
+                    let expected = decoder.decode(&invalid);
+                    let decoded = decoder.decode_in_place(&mut invalid).map(|d| d.to_vec());
+                    assert_eq!(format!("{decoded:?}"), format!("{expected:?}"), "len {len}");
+                }
+            }
+        }
+    }
+
+    let mut buf = *b"aGVsbG8=";
+    assert_eq!(bs64::decode_in_place(&mut buf).unwrap(), b"hello");
+}
+
+#[test]
+fn encode_in_place_matches_encode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let encoders = [
+        EncodeOptions::default(),
+        EncodeOptions::default().padding(false),
+        EncodeOptions::default().alphabet(&URL_SAFE),
+        EncodeOptions::default().line_wrap(LineWrap::MIME),
+        EncodeOptions::default().line_wrap(LineWrap::new(4, LineEnding::CrLf)),
+    ];
+    for len in (0..300).chain([3071, 3072, 3073, 5000, 100_000]) {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+
+        for encoder in encoders {
+            let expected = encoder.encode(&input);
+            let mut buf = vec![0u8; expected.len() + 3];
+            buf[..len].copy_from_slice(&input);
+            assert_eq!(
+                encoder.encode_in_place(&mut buf, len).unwrap(),
+                expected.len()
+            );
+            assert_eq!(&buf[..expected.len()], expected.as_bytes(), "len {len}");
+
+            if !expected.is_empty() {
+                let mut short = input.clone();
+                short.resize(expected.len() - 1, 0);
+                assert!(matches!(
+                    encoder.encode_in_place(&mut short, len),
+                    Err(CodecError::OutputLengthTooShort(_, _))
+                ));
+            }
+        }
+    }
+
+    let mut buf = *b"hello\0\0\0";
+    assert_eq!(bs64::encode_in_place(&mut buf, 5).unwrap(), 8);
+    assert_eq!(&buf, b"aGVsbG8=");
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "serde")]
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Fields {
+    #[serde(with = "bs64::serde::standard")]
+    standard: Vec<u8>,
+    #[serde(with = "bs64::serde::standard_no_pad")]
+    standard_no_pad: Vec<u8>,
+    #[serde(with = "bs64::serde::url_safe")]
+    url_safe: [u8; 4],
+    #[serde(with = "bs64::serde::url_safe_no_pad")]
+    url_safe_no_pad: Box<[u8]>,
+}
+
+#[test]
+fn serde_round_trip() {
+    let fields = Fields {
+        standard: vec![0xfb, 0xff, 0xfe],
+        standard_no_pad: b"hello".to_vec(),
+        url_safe: [0xfb, 0xff, 0, 1],
+        url_safe_no_pad: Box::new([0xfb, 0xff]),
+    };
+    let json = serde_json::to_string(&fields).unwrap();
+    assert_eq!(
+        json,
+        r#"{"standard":"+//+","standard_no_pad":"aGVsbG8","url_safe":"-_8AAQ==","url_safe_no_pad":"-_8"}"#
+    );
+    assert_eq!(serde_json::from_str::<Fields>(&json).unwrap(), fields);
+
+    // Escaped strings can't be borrowed from the input
+    let escaped = json.replace("aGVsbG8", "aGVsb\\u0047\\u0038");
+    let decoded: Fields = serde_json::from_str(&escaped).unwrap();
+    assert_eq!(decoded.standard_no_pad, b"hello");
+}
+
+#[test]
+fn serde_rejects_invalid_input() {
+    let json = r#"{"standard":"+//+","standard_no_pad":"aGVsbG8=","url_safe":"-_8AAQ==","url_safe_no_pad":"-_8"}"#;
+    let err = serde_json::from_str::<Fields>(json).unwrap_err();
+    assert!(err.to_string().contains("invalid padding"), "{err}");
+
+    let json = r#"{"standard":"+//+","standard_no_pad":"aGVsbG8","url_safe":"-_8AAQE=","url_safe_no_pad":"-_8"}"#;
+    let err = serde_json::from_str::<Fields>(json).unwrap_err();
+    assert!(err.to_string().contains("invalid length 5"), "{err}");
+}
@@ -1,11 +1,13 @@
-use bs64;
+use bs64::CodecError;
 use data_encoding::BASE64;
 use rand::prelude::*;
+use std::mem::MaybeUninit;
 
 const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 const NON_VALID_CHARS: &[u8; 30] = b"!@#$%^&*()_{}|:<>?~`-=[]\\;',.\"";
 
 #[test]
+#[cfg(feature = "alloc")]
 fn zero_len() {
     let input = b"";
     let expected = b"";
@@ -42,7 +44,94 @@ fn length_to_1000_simple() {
     }
 }
 
+/// Needs no features, so `cargo test --no-default-features` runs the dispatch
+/// through the cpuid based detection
 #[test]
+fn round_trip_without_alloc() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let mut input = [0u8; 300];
+    let mut encoded = [0u8; bs64::encode_len(300)];
+    let mut decoded = [0u8; 300];
+    for i in 0..=input.len() {
+        let input = &mut input[..i];
+        rng.fill(&mut input[..]);
+        let expected = BASE64.encode(input);
+
+        let len = bs64::encode_mut(input, &mut encoded).unwrap();
+        assert_eq!(&encoded[..len], expected.as_bytes());
+        assert_eq!(bs64::decode_mut(&encoded[..len], &mut decoded).unwrap(), i);
+        assert_eq!(&decoded[..i], input);
+
+        let len = bs64::simple::encode(input, &mut encoded);
+        assert_eq!(&encoded[..len], expected.as_bytes());
+        assert_eq!(
+            bs64::simple::decode(&encoded[..len], &mut decoded).unwrap(),
+            i
+        );
+        assert_eq!(&decoded[..i], input);
+    }
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn length_to_1000_avx2() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    for i in 0..1000 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+
+        let expected = BASE64.encode(&input);
+        let mut output = vec![0u8; bs64::encode_len(input.len())];
+        assert_eq!(
+            bs64::avx2::encode_with_fallback(&mut output, &input),
+            output.len()
+        );
+        assert_eq!(output, expected.as_bytes());
+
+        let mut decoded = vec![0u8; i];
+        assert_eq!(
+            bs64::avx2::decode_with_fallback(&mut decoded, &output).unwrap(),
+            i
+        );
+        assert_eq!(decoded, input);
+
+        if !bs64::avx2::has_avx2() {
+            continue;
+        }
+        let mut output = vec![0u8; bs64::encode_len(input.len())];
+        unsafe { bs64::avx2::encode(&mut output, &input) };
+        assert_eq!(output, expected.as_bytes());
+
+        let mut decoded = vec![0u8; i];
+        unsafe { bs64::avx2::decode(&mut decoded, &output) }.unwrap();
+        assert_eq!(decoded, input);
+    }
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn length_to_1000_sse() {
+    if !bs64::sse::has_ssse3() {
+        return;
+    }
+    let mut rng = SmallRng::seed_from_u64(20);
+    for i in 0..1000 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+
+        let expected = BASE64.encode(&input);
+        let mut output = vec![0u8; bs64::encode_len(input.len())];
+        unsafe { bs64::sse::encode(&mut output, &input) };
+        assert_eq!(output, expected.as_bytes());
+
+        let mut decoded = vec![0u8; i];
+        unsafe { bs64::sse::decode(&mut decoded, &output) }.unwrap();
+        assert_eq!(decoded, input);
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
 fn length_to_1000() {
     let mut rng = SmallRng::seed_from_u64(20);
     for i in 64..1000 {
@@ -72,6 +161,63 @@ fn output_size() {
     }
 }
 
+#[test]
+fn uninit_output() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    for i in 0..200 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+        let expected = BASE64.encode(&input);
+
+        let mut output = Vec::with_capacity(bs64::encode_len(i) + 10);
+        let encoded = bs64::encode_uninit(&input, output.spare_capacity_mut()).unwrap();
+        assert_eq!(encoded, expected.as_bytes());
+
+        let mut decoded = Vec::with_capacity(i);
+        let decoded = bs64::decode_uninit(expected.as_bytes(), decoded.spare_capacity_mut());
+        assert_eq!(decoded.unwrap(), input);
+    }
+
+    let mut output = [MaybeUninit::uninit(); 3];
+    assert!(matches!(
+        bs64::encode_uninit(b"abc", &mut output),
+        Err(CodecError::OutputLengthTooShort(3, 4))
+    ));
+    assert!(matches!(
+        bs64::decode_uninit(b"YWJjZA==", &mut output),
+        Err(CodecError::OutputLengthTooShort(3, 4))
+    ));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn append_to_buffers() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let mut encoded = String::from("prefix:");
+    let mut decoded = vec![1, 2, 3];
+    let mut expected_encoded = encoded.clone();
+    let mut expected_decoded = decoded.clone();
+    for i in 0..200 {
+        let mut input = vec![0u8; i];
+        rng.fill(&mut input[..]);
+        let expected = BASE64.encode(&input);
+        expected_encoded.push_str(&expected);
+        expected_decoded.extend_from_slice(&input);
+
+        assert_eq!(bs64::encode_to_string(&input, &mut encoded), expected.len());
+        assert_eq!(
+            bs64::decode_to_vec(expected.as_bytes(), &mut decoded).unwrap(),
+            i
+        );
+    }
+    assert_eq!(encoded, expected_encoded);
+    assert_eq!(decoded, expected_decoded);
+
+    // Errors leave the output as it was
+    assert!(bs64::decode_to_vec(b"YW*j", &mut decoded).is_err());
+    assert_eq!(decoded, expected_decoded);
+}
+
 #[test]
 fn garbage_decode_results_in_error() {
     let mut rng = SmallRng::seed_from_u64(20);
@@ -91,3 +237,81 @@ fn garbage_decode_results_in_error() {
         assert!(is_err);
     }
 }
+
+#[test]
+fn invalid_byte_reports_offset() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    for i in 4..400 {
+        let mut data = vec![0u8; i];
+        rng.fill(&mut data[..]);
+        let mut input = BASE64.encode(&data).into_bytes();
+        let mut output = vec![0u8; bs64::decode_len(input.len())];
+
+        // Keep clear of the last group so the padding stays valid
+        let j = rng.gen_range(0..input.len() - 4);
+        let byte = *NON_VALID_CHARS.choose(&mut rng).unwrap();
+        input[j] = byte;
+
+        let results = [
+            bs64::decode_mut(&input, &mut output),
+            bs64::simple::decode(&input, &mut output),
+            bs64::simple::decode_iter(&input, &mut output),
+        ];
+        for result in results {
+            match result {
+                Err(bs64::CodecError::InvalidByte { offset, byte: b }) => {
+                    assert_eq!((offset, b), (j, byte), "length {i}")
+                }
+                other => panic!("length {i}: expected invalid byte at {j}, got {other:?}"),
+            }
+        }
+    }
+}
+
+/// The safe backend functions check the output length before writing to it
+#[test]
+fn short_output_is_rejected() {
+    use bs64::alphabet::STANDARD;
+
+    let data = [0u8; 48];
+    let encoded = [b'A'; 64];
+    let mut buf = [0x55u8; 128];
+    let (dest, canary) = buf.split_at_mut(4);
+    let results = [
+        bs64::simple::encode_with_alphabet(&data, dest, &STANDARD),
+        bs64::simple::decode_with_alphabet(&encoded, dest, &STANDARD),
+        bs64::simple::decode_iter_with_alphabet(&encoded, dest, &STANDARD),
+        bs64::simple::decode(&encoded, dest),
+        #[cfg(target_arch = "x86_64")]
+        bs64::avx2::encode_with_alphabet_with_fallback(dest, &data, &STANDARD),
+        #[cfg(target_arch = "x86_64")]
+        bs64::avx2::decode_with_alphabet_with_fallback(dest, &encoded, &STANDARD),
+        #[cfg(target_arch = "x86_64")]
+        bs64::avx2::decode_with_fallback(dest, &encoded),
+        bs64::simple::encode_wrapped(&data, dest, &STANDARD, bs64::LineWrap::PEM),
+        #[cfg(target_arch = "x86_64")]
+        bs64::avx2::encode_wrapped_with_fallback(dest, &data, &STANDARD, bs64::LineWrap::PEM),
+    ];
+    for result in results {
+        match result {
+            Err(CodecError::OutputLengthTooShort(4, len)) => assert!([48, 64, 66].contains(&len)),
+            other => panic!("expected output length error, got {other:?}"),
+        }
+    }
+    assert!(canary.iter().all(|&c| c == 0x55));
+}
+
+#[test]
+#[should_panic(expected = "output length 4 is < expected length 64")]
+fn simple_encode_panics_on_short_output() {
+    bs64::simple::encode(&[0u8; 48], &mut [0u8; 4]);
+}
+
+/// Without the std feature, e.g. under `--no-default-features`, this checks the
+/// cpuid based detection
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn simd_detection_matches_std() {
+    assert_eq!(bs64::avx2::has_avx2(), is_x86_feature_detected!("avx2"));
+    assert_eq!(bs64::sse::has_ssse3(), is_x86_feature_detected!("ssse3"));
+}
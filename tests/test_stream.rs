@@ -0,0 +1,399 @@
+#![cfg(feature = "std")]
+
+use std::io::{self, Read, Write};
+
+use bs64::alphabet::URL_SAFE;
+use bs64::{
+    CodecError, DecodeOptions, Decoder, DecoderReader, EncodeOptions, Encoder, EncoderWriter,
+    LineEnding, LineWrap, Padding,
+};
+use rand::prelude::*;
+
+fn encode_options() -> [EncodeOptions<'static>; 5] {
+    [
+        EncodeOptions::default(),
+        EncodeOptions::default().alphabet(&URL_SAFE).padding(false),
+        EncodeOptions::default().line_wrap(LineWrap::MIME),
+        EncodeOptions::default()
+            .line_wrap(LineWrap::PEM)
+            .padding(false),
+        EncodeOptions::default().line_wrap(LineWrap::new(4, LineEnding::CrLf)),
+    ]
+}
+
+/// Writes at most 7 bytes at a time, and is interrupted every other call
+struct ShortWriter {
+    output: Vec<u8>,
+    interrupt: bool,
+}
+
+impl Write for ShortWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.interrupt = !self.interrupt;
+        if self.interrupt {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let len = buf.len().min(7);
+        self.output.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn encoder_writer_matches_encode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    for len in [0, 1, 2, 3, 4, 100, 1000, 30000, 100000] {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+
+        for options in encode_options() {
+            let expected = options.encode(&input);
+
+            let mut writer = EncoderWriter::new(Vec::new(), options);
+            let mut rest = &input[..];
+            while !rest.is_empty() {
+                let (chunk, tail) = rest.split_at(rng.gen_range(0..=rest.len().min(5000)));
+                writer.write_all(chunk).unwrap();
+                rest = tail;
+            }
+            let output = writer.finish().unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), expected, "length {len}");
+        }
+    }
+}
+
+#[test]
+fn encoder_writer_handles_short_writes() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let mut input = vec![0u8; 10000];
+    rng.fill(&mut input[..]);
+
+    for options in encode_options() {
+        let inner = ShortWriter {
+            output: Vec::new(),
+            interrupt: false,
+        };
+        let mut writer = EncoderWriter::new(inner, options);
+        for chunk in input.chunks(1001) {
+            writer.write_all(chunk).unwrap();
+        }
+        let output = writer.finish().unwrap().output;
+        assert_eq!(output, options.encode(&input).as_bytes());
+    }
+}
+
+#[test]
+fn encoder_writer_finishes_on_drop() {
+    let mut output = Vec::new();
+    {
+        let mut writer = EncoderWriter::new(&mut output, EncodeOptions::default());
+        writer.write_all(b"hello").unwrap();
+    }
+    assert_eq!(output, b"aGVsbG8=");
+}
+
+/// Reads at most 1 to 100 bytes at a time
+struct ShortReader<'a> {
+    input: &'a [u8],
+    rng: SmallRng,
+}
+
+impl Read for ShortReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.input.len())
+            .min(self.rng.gen_range(1..100));
+        buf[..len].copy_from_slice(&self.input[..len]);
+        self.input = &self.input[len..];
+        Ok(len)
+    }
+}
+
+/// Read everything from a DecoderReader, with reads of random sizes
+fn read_all(input: &[u8], options: DecodeOptions) -> Result<Vec<u8>, CodecError> {
+    let mut rng = SmallRng::seed_from_u64(input.len() as u64);
+    let inner = ShortReader {
+        input,
+        rng: rng.clone(),
+    };
+    let mut reader = DecoderReader::new(inner, options);
+    let mut output = Vec::new();
+    let mut buf = vec![0u8; 50000];
+    loop {
+        let len = rng.gen_range(1..buf.len());
+        match reader.read(&mut buf[..len]) {
+            Ok(0) => return Ok(output),
+            Ok(n) => output.extend_from_slice(&buf[..n]),
+            Err(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                return Err(*e.into_inner().unwrap().downcast::<CodecError>().unwrap());
+            }
+        }
+    }
+}
+
+#[test]
+fn decoder_reader_matches_decode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let cases = [
+        (EncodeOptions::default(), DecodeOptions::default()),
+        (
+            EncodeOptions::default().alphabet(&URL_SAFE).padding(false),
+            DecodeOptions::default()
+                .alphabet(&URL_SAFE)
+                .padding(Padding::Forbidden),
+        ),
+        (
+            EncodeOptions::default().line_wrap(LineWrap::MIME),
+            DecodeOptions::default().ignore_whitespace(true),
+        ),
+        (
+            EncodeOptions::default().line_wrap(LineWrap::new(4, LineEnding::CrLf)),
+            DecodeOptions::default()
+                .ignore_whitespace(true)
+                .canonical(true),
+        ),
+    ];
+    for len in [0, 1, 2, 3, 4, 100, 1000, 100000] {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+
+        for (encoder, decoder) in cases {
+            let encoded = encoder.encode(&input);
+            let output = read_all(encoded.as_bytes(), decoder).unwrap();
+            assert_eq!(output, input, "length {len}");
+        }
+    }
+}
+
+#[test]
+fn decoder_reader_errors_match_decode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let cases = [
+        (EncodeOptions::default(), DecodeOptions::default()),
+        (
+            EncodeOptions::default().line_wrap(LineWrap::PEM),
+            DecodeOptions::default().ignore_whitespace(true),
+        ),
+    ];
+    for (encoder, decoder) in cases {
+        let encoded = encoder.encode(&[0xa5; 2000]).into_bytes();
+        for _ in 0..200 {
+            let mut input = encoded.clone();
+            let j = rng.gen_range(0..input.len());
+            input[j] = b'*';
+            assert_eq!(
+                read_all(&input, decoder).map_err(|e| e.to_string()),
+                decoder.decode(&input).map_err(|e| e.to_string())
+            );
+
+            // Errors are found in a different order, but the same inputs are rejected
+            input[j] = *b"=A \n".choose(&mut rng).unwrap();
+            input.truncate(rng.gen_range(j..=input.len()));
+            assert_eq!(read_all(&input, decoder).ok(), decoder.decode(&input).ok());
+        }
+    }
+}
+
+#[test]
+fn decoder_reader_repeats_first_error() {
+    let mut input = vec![b'A'; 1000];
+    input[10] = b'*';
+    let mut reader = DecoderReader::new(&input[..], DecodeOptions::default());
+    let mut buf = [0u8; 4];
+    for _ in 0..3 {
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = err.into_inner().unwrap().downcast::<CodecError>().unwrap();
+        assert!(
+            matches!(
+                *err,
+                CodecError::InvalidByte {
+                    offset: 10,
+                    byte: b'*'
+                }
+            ),
+            "{err:?}"
+        );
+    }
+}
+
+#[test]
+fn decoder_reader_streams_long_trailers() {
+    // Trailing whitespace and padding used to be buffered and rescanned on every read
+    let len = 16 << 20;
+    let read = |head: &'static [u8], fill: u8, options: DecodeOptions| {
+        let inner = head.chain(io::repeat(fill).take(len));
+        let mut output = Vec::new();
+        DecoderReader::new(inner, options)
+            .read_to_end(&mut output)
+            .map(|_| output)
+    };
+
+    let whitespace = DecodeOptions::default().ignore_whitespace(true);
+    assert_eq!(read(b"QUJD", b' ', whitespace).unwrap(), b"ABC");
+    assert_eq!(read(b"QQ==", b'\n', whitespace).unwrap(), b"A");
+
+    let err = read(b"QUJDQQ", b'=', DecodeOptions::default()).unwrap_err();
+    let err = err.into_inner().unwrap().downcast::<CodecError>().unwrap();
+    assert!(matches!(*err, CodecError::InvalidPadding(6)), "{err:?}");
+}
+
+/// Split input into 0 to 9 fragments at random points
+fn fragments<'a>(input: &'a [u8], rng: &mut SmallRng) -> Vec<&'a [u8]> {
+    let mut points: Vec<usize> = (0..rng.gen_range(0..10))
+        .map(|_| rng.gen_range(0..=input.len()))
+        .collect();
+    points.sort();
+    points.push(input.len());
+    let mut start = 0;
+    points
+        .into_iter()
+        .map(|end| {
+            let fragment = &input[start..end];
+            start = end;
+            fragment
+        })
+        .collect()
+}
+
+#[test]
+fn encoder_matches_encode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    for len in 0..300 {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+
+        for options in encode_options() {
+            let mut encoder = Encoder::new(options);
+            let mut output = Vec::new();
+            for fragment in fragments(&input, &mut rng) {
+                let mut buf = vec![0u8; encoder.update_len(fragment.len())];
+                let len = encoder.update(fragment, &mut buf).unwrap();
+                output.extend_from_slice(&buf[..len]);
+            }
+            let mut buf = vec![0u8; encoder.finalize_len()];
+            let len = encoder.finalize(&mut buf).unwrap();
+            output.extend_from_slice(&buf[..len]);
+            assert_eq!(String::from_utf8(output).unwrap(), options.encode(&input));
+        }
+    }
+}
+
+fn decode_fragments(fragments: &[&[u8]], options: DecodeOptions) -> Result<Vec<u8>, CodecError> {
+    let mut decoder = Decoder::new(options);
+    let mut output = Vec::new();
+    for fragment in fragments {
+        let mut buf = vec![0u8; decoder.update_len(fragment.len())];
+        let len = decoder.update(fragment, &mut buf)?;
+        output.extend_from_slice(&buf[..len]);
+    }
+    let mut buf = vec![0u8; decoder.finalize_len()];
+    let len = decoder.finalize(&mut buf)?;
+    output.extend_from_slice(&buf[..len]);
+    Ok(output)
+}
+
+#[test]
+fn decoder_matches_decode() {
+    let mut rng = SmallRng::seed_from_u64(20);
+    let cases = [
+        (EncodeOptions::default(), DecodeOptions::default()),
+        (
+            EncodeOptions::default().alphabet(&URL_SAFE).padding(false),
+            DecodeOptions::default()
+                .alphabet(&URL_SAFE)
+                .padding(Padding::Forbidden),
+        ),
+        (
+            EncodeOptions::default().line_wrap(LineWrap::new(4, LineEnding::CrLf)),
+            DecodeOptions::default()
+                .ignore_whitespace(true)
+                .canonical(true),
+        ),
+    ];
+    for len in 0..300 {
+        let mut input = vec![0u8; len];
+        rng.fill(&mut input[..]);
+
+        for (encoder, decoder) in cases {
+            let encoded = encoder.encode(&input);
+            let fragments = fragments(encoded.as_bytes(), &mut rng);
+            assert_eq!(decode_fragments(&fragments, decoder).unwrap(), input);
+
+            // Errors are the same, including the offset
+            let mut invalid = encoded.clone().into_bytes();
+            if !invalid.is_empty() {
+                let j = rng.gen_range(0..invalid.len());
+                invalid[j] = *b"*=A\n".choose(&mut rng).unwrap();
+                let fragments = self::fragments(&invalid, &mut rng);
+                assert_eq!(
+                    decode_fragments(&fragments, decoder).map_err(|e| e.to_string()),
+                    decoder.decode(&invalid).map_err(|e| e.to_string()),
+                    "{}",
+                    String::from_utf8_lossy(&invalid)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn decoder_reports_invalid_chars_before_length() {
+    let options = DecodeOptions::default();
+    let mut decoder = Decoder::new(options);
+    assert!(matches!(
+        decoder.update(b"\n", &mut []),
+        Err(CodecError::InvalidByte {
+            offset: 0,
+            byte: b'\n'
+        })
+    ));
+    assert!(matches!(
+        options.decode(b"\n"),
+        Err(CodecError::InputModError(1))
+    ));
+
+    // An input with a single problem fails the same way
+    for options in [options, options.ignore_whitespace(true)] {
+        assert_eq!(
+            decode_fragments(&[b"QU", b"J*"], options).map_err(|e| e.to_string()),
+            options.decode(b"QUJ*").map_err(|e| e.to_string())
+        );
+    }
+}
+
+#[test]
+fn incremental_output_too_short() {
+    let mut encoder = Encoder::new(EncodeOptions::default());
+    assert!(matches!(
+        encoder.update(b"hello", &mut [0u8; 3]),
+        Err(CodecError::OutputLengthTooShort(3, 4))
+    ));
+    assert_eq!(encoder.update(b"hel", &mut [0u8; 4]).unwrap(), 4);
+    assert_eq!(encoder.update(b"lo", &mut []).unwrap(), 0);
+    assert!(matches!(
+        encoder.finalize(&mut [0u8; 3]),
+        Err(CodecError::OutputLengthTooShort(3, 4))
+    ));
+
+    // Only the chars that are decoded need room, not padding or whitespace
+    let mut output = [0u8; 5];
+    let mut decoder = Decoder::new(DecodeOptions::default());
+    let len = decoder.update(b"aGVsbG8=", &mut output).unwrap();
+    assert_eq!(len + decoder.finalize(&mut output[len..]).unwrap(), 5);
+    assert_eq!(&output, b"hello");
+
+    let mut decoder = Decoder::new(DecodeOptions::default().ignore_whitespace(true));
+    let len = decoder.update(b"aGVs\r\nbG8=\r\n", &mut output).unwrap();
+    assert_eq!(len + decoder.finalize(&mut output[len..]).unwrap(), 5);
+    assert!(matches!(
+        decoder.update(b"aGVs bG8g", &mut output),
+        Err(CodecError::OutputLengthTooShort(5, 6))
+    ));
+}